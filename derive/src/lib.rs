@@ -1,5 +1,6 @@
 #![feature(iter_array_chunks)]
 
+mod migrations;
 mod scylla;
 
 use syn::Type;
@@ -14,6 +15,16 @@ pub fn derive_map_to_type(input: proc_macro::TokenStream) -> proc_macro::TokenSt
     scylla::map_to_type(input)
 }
 
+/// Embeds the `.cql` files of a directory, relative to the manifest of the crate, ordered by
+/// the number their names start with.
+///
+/// Cargo rebuilds when an embedded file changes but not when a file is added, a build script
+/// printing `cargo::rerun-if-changed=<directory>` takes care of that.
+#[proc_macro]
+pub fn migrations(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    migrations::migrations(input)
+}
+
 fn is_option(ty: &Type) -> bool {
     match ty {
         Type::Path(path) => path
@@ -0,0 +1,89 @@
+use quote::quote;
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
+use syn::{Error, LitStr, parse_macro_input};
+
+pub fn migrations(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+
+    match files(&path) {
+        Ok(files) => {
+            let files = files.into_values().map(|(name, path)| {
+                quote! {
+                    lib_persist::scylla::Migration { file: #name, cql: include_str!(#path) }
+                }
+            });
+            quote! { lib_persist::scylla::Migrations::new(&[#(#files),*]) }
+        }
+        Err(error) => error.to_compile_error(),
+    }
+    .into()
+}
+
+/// `.cql` files in the directory, relative to the manifest of the crate being compiled, keyed
+/// by their numeric prefix.
+fn files(path: &LitStr) -> Result<BTreeMap<u64, (String, String)>, Error> {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(path.value());
+    let entries = fs::read_dir(&dir).map_err(|error| {
+        Error::new(
+            path.span(),
+            format!("Could not read {}: {error}", dir.display()),
+        )
+    })?;
+
+    let mut files = BTreeMap::new();
+    for entry in entries {
+        let entry_path = entry
+            .map_err(|error| {
+                Error::new(
+                    path.span(),
+                    format!("Could not read {}: {error}", dir.display()),
+                )
+            })?
+            .path();
+        if entry_path
+            .extension()
+            .is_none_or(|extension| extension != "cql")
+        {
+            continue;
+        }
+
+        let name = entry_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                Error::new(
+                    path.span(),
+                    format!("Invalid file name {}", entry_path.display()),
+                )
+            })?
+            .to_string();
+        let index = name
+            .split(|char: char| !char.is_ascii_digit())
+            .next()
+            .and_then(|prefix| prefix.parse::<u64>().ok())
+            .ok_or_else(|| {
+                Error::new(
+                    path.span(),
+                    format!("Migration '{name}' doesn't start with a number"),
+                )
+            })?;
+        let entry_path = entry_path
+            .to_str()
+            .ok_or_else(|| {
+                Error::new(
+                    path.span(),
+                    format!("Invalid path {}", entry_path.display()),
+                )
+            })?
+            .to_string();
+
+        if let Some((other, _)) = files.insert(index, (name.clone(), entry_path)) {
+            return Err(Error::new(
+                path.span(),
+                format!("Migrations '{other}' and '{name}' have the same number"),
+            ));
+        }
+    }
+
+    Ok(files)
+}
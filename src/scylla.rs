@@ -1,23 +1,85 @@
+mod batch;
+mod classify;
+mod conditional;
+mod config;
+mod continuation;
+mod cursor;
+mod metrics;
+mod migrations;
+mod options;
+mod prepared;
+mod redaction;
+mod retry;
+mod script;
+mod slow_query;
+mod trace;
+
 use base62::encode;
-use futures::{StreamExt, TryStreamExt, stream::iter};
-pub use lib_persist_derive::{MapToScyllaRow, MapToScyllaType};
-pub use scylla::{client::pager::QueryPager, statement::prepared::PreparedStatement};
+pub use batch::Batch;
+pub use classify::{Classify, ErrorKind};
+pub use conditional::Conditional;
+use config::CredentialsProvider;
+pub use config::{
+    Compression, ConnectRetry, Credentials, InstanceConfig, LoadBalancing, TlsConfig,
+};
+pub use continuation::ContinuationToken;
+use continuation::TokenSigner;
+pub use cursor::{Cursor, Page};
+use futures::{
+    Stream, StreamExt, TryStreamExt,
+    future::try_join_all,
+    stream::{iter, try_unfold},
+};
+pub use lib_persist_derive::{MapToScyllaRow, MapToScyllaType, migrations};
+use metrics::PreparedTexts;
+pub use metrics::{MetricsRecorder, PrometheusRecorder};
+use migrations::Lease;
+pub use migrations::{Migration, MigrationLock, MigrationSummary, Migrations};
+pub use options::QueryOptions;
+pub use prepared::Prepared;
+pub use redaction::Redaction;
+use retry::SpeculativeProfiles;
+pub use retry::{RetryPolicy, SpeculativeExecution};
+pub use scylla::{
+    client::pager::QueryPager,
+    statement::{batch::BatchType, prepared::PreparedStatement},
+};
 use scylla::{
     client::{
         caching_session::{CachingSession, CachingSessionBuilder},
+        session::Session,
         session_builder::SessionBuilder,
     },
-    frame::Compression,
-    response::{PagingState, query_result::QueryResult},
-    serialize::row::SerializeRow,
-    statement::Statement,
+    deserialize::row::DeserializeRow,
+    errors::{BadQuery, NewSessionError},
+    response::{PagingState, PagingStateResponse, query_result::QueryResult},
+    serialize::{
+        row::{RowSerializationContext, SerializeRow},
+        writers::RowWriter,
+    },
+    statement::{Consistency, Statement},
     value::CqlTimestamp,
 };
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use sha2::{Digest, Sha256};
+use slow_query::Execution;
+pub use slow_query::SlowQueryLog;
+use std::{
+    collections::HashMap,
+    future::Future,
+    num::NonZeroU32,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tinytemplate::TinyTemplate;
-use tracing::{debug, error, instrument};
+use tokio::time::sleep;
+pub use trace::{Trace, TraceEvent};
+use tracing::{debug, error, instrument, warn};
 use uuid::Uuid;
 
+pub const ENV_PREFIX: &str = "LIB_PERSIST";
+
 #[derive(thiserror::Error, Debug)]
 pub enum MappingError {
     #[error("Value is missing for field {0}")]
@@ -27,31 +89,122 @@ pub enum MappingError {
 }
 
 #[derive(thiserror::Error, Debug)]
-#[error("{error}")]
+#[error("Could not prepare '{query}': {error}")]
 pub struct PrepareError {
     query: String,
     #[source]
     error: scylla::errors::PrepareError,
 }
 
+impl PrepareError {
+    #[must_use]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Could not read {}: {error}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error("Could not parse {}: {error}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        error: toml::de::Error,
+    },
+    #[error("Missing value for {0}")]
+    MissingKey(String),
+    #[error("Invalid value for {key}: {reason}")]
+    InvalidValue { key: String, reason: String },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TlsError {
+    #[error("Could not read {}: {error}", path.display())]
+    Pem {
+        path: PathBuf,
+        #[source]
+        error: rustls::pki_types::pem::Error,
+    },
+    #[error("No certificates found in {}", .0.display())]
+    NoCertificates(PathBuf),
+    #[error("{0}")]
+    Verifier(#[from] rustls::client::VerifierBuilderError),
+    #[error("{0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid cursor")]
+pub struct CursorError;
+
 #[derive(thiserror::Error, Debug)]
 pub enum LoadError {
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    Tls(#[from] TlsError),
     #[error("{0}")]
     Load(#[from] scylla::errors::NewSessionError),
+    #[error("Cluster unreachable after {attempts} attempts in {elapsed:?}: {error}")]
+    Unreachable {
+        attempts: u32,
+        elapsed: Duration,
+        #[source]
+        error: scylla::errors::NewSessionError,
+    },
     #[error("{0}")]
     KeyspaceSetup(#[from] scylla::errors::UseKeyspaceError),
 }
 
+/// Malformed CQL script, such as a string that is never closed.
+#[derive(thiserror::Error, Debug)]
+#[error("Unterminated {token} starting at line {line}, column {column}")]
+pub struct ScriptError {
+    token: &'static str,
+    line: usize,
+    column: usize,
+}
+
+impl ScriptError {
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+
+    #[must_use]
+    pub const fn column(&self) -> usize {
+        self.column
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum SetupError {
     #[error("{0}")]
     Execution(#[from] scylla::errors::ExecutionError),
     #[error("{0}")]
     Result(#[from] scylla::response::query_result::IntoRowsResultError),
+    #[error("{0}")]
+    Row(#[from] scylla::errors::SingleRowError),
+    #[error("{0}")]
+    Script(#[from] ScriptError),
+    #[error("{0}")]
+    SchemaAgreement(#[from] scylla::errors::SchemaAgreementError),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum MigrationError {
+    #[error("Migration {file}: {error}")]
+    Script {
+        file: String,
+        #[source]
+        error: ScriptError,
+    },
     #[error("Migration {file}:{index} '{statement}' returned {error}")]
     Migration {
         file: String,
@@ -60,12 +213,29 @@ pub enum MigrationError {
         error: scylla::errors::ExecutionError,
         statement: String,
     },
+    #[error("Migration {file}:{index} did not reach schema agreement: {error}")]
+    SchemaAgreement {
+        file: String,
+        index: i32,
+        #[source]
+        error: scylla::errors::SchemaAgreementError,
+    },
+    #[error("Migration {file}:{index} was changed after it was applied")]
+    ChecksumMismatch { file: String, index: i32 },
+    #[error("Migrations are locked by another runner")]
+    Locked { owner: Option<Uuid> },
+    #[error("Migration lock expired while migrating")]
+    LockLost,
     #[error("{0}")]
     Execution(#[from] scylla::errors::ExecutionError),
     #[error("{0}")]
     VersionQuery(#[from] scylla::errors::MaybeFirstRowError),
     #[error("{0}")]
     Result(#[from] scylla::response::query_result::IntoRowsResultError),
+    #[error("{0}")]
+    Rows(#[from] scylla::errors::RowsError),
+    #[error("{0}")]
+    Deserialization(#[from] scylla::errors::DeserializationError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -82,50 +252,139 @@ pub enum Error {
     NextRow(#[from] scylla::errors::NextRowError),
     #[error("{0}")]
     TypeCheck(#[from] scylla::errors::TypeCheckError),
+    #[error("{0}")]
+    Rows(#[from] scylla::errors::RowsError),
+    #[error("{0}")]
+    Deserialization(#[from] scylla::errors::DeserializationError),
+    #[error("{0}")]
+    Trace(#[from] scylla::errors::TracingError),
+    #[error("Batch statement {index} '{statement}' returned {error}")]
+    Batch {
+        index: usize,
+        statement: String,
+        #[source]
+        error: scylla::errors::ExecutionError,
+    },
+    #[error("Query returned no rows")]
+    NoRows,
+    #[error("Query returned at least {0} rows, expected at most one")]
+    TooManyRows(usize),
+    #[error("Continuation token does not match the query")]
+    TokenMismatch,
+    #[error("Continuation token has expired")]
+    TokenExpired,
+    #[error("No continuation key is configured")]
+    ContinuationKeyMissing,
+}
+
+/// Any error returned by the crate, every error type converts into it.
+#[derive(thiserror::Error, Debug)]
+pub enum PersistError {
+    #[error("{0}")]
+    Mapping(#[from] MappingError),
+    #[error("{0}")]
+    Prepare(#[from] PrepareError),
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    Tls(#[from] TlsError),
+    #[error("{0}")]
+    Cursor(#[from] CursorError),
+    #[error("{0}")]
+    Load(#[from] LoadError),
+    #[error("{0}")]
+    Setup(#[from] SetupError),
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
+    #[error("{0}")]
+    Query(#[from] Error),
 }
 
 #[derive(Clone)]
+#[allow(clippy::struct_field_names)]
 pub struct Instance {
     inner: Arc<CachingSession>,
     app_name: Arc<str>,
     app_instance: Uuid,
     app_version: Arc<str>,
+    replication_factor: usize,
+    implementation: Option<Arc<str>>,
+    credentials: Arc<CredentialsProvider>,
+    query_options: QueryOptions,
+    speculative_profiles: Arc<SpeculativeProfiles>,
+    prepared_texts: Arc<PreparedTexts>,
+    token_signer: Option<Arc<TokenSigner>>,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    slow_query_log: Option<SlowQueryLog>,
+    consistency: Consistency,
+    redaction: Redaction,
+    repair_migrations: bool,
+    migration_lock: MigrationLock,
 }
 
 impl Instance {
-    #[instrument(skip(credentials), err)]
-    pub async fn new(
-        app_instance: Uuid,
-        app_name: String,
-        app_version: String,
-        nodes: &[impl AsRef<str> + std::fmt::Debug + Send + Sync],
-        credentials: Option<(
-            impl Into<String> + Send + Sync,
-            impl Into<String> + Send + Sync,
-        )>,
-    ) -> Result<Self, LoadError> {
-        let mut builder = SessionBuilder::new()
-            .known_nodes(nodes)
-            .connection_timeout(Duration::from_secs(30))
-            .compression(Some(Compression::Lz4));
-
-        if let Some((username, password)) = credentials {
-            builder = builder.user(username, password);
-        }
+    #[instrument(skip_all, fields(app_name = config.app_name, nodes = ?config.nodes), err)]
+    pub async fn connect(config: InstanceConfig) -> Result<Self, LoadError> {
+        let credentials = Arc::new(CredentialsProvider::new(config.credentials.clone()));
+        let query_options = config.query_options();
+        let session = build_session(
+            &config.session_builder(credentials.clone())?,
+            config.connect_retry,
+        )
+        .await?;
+        let speculative_profiles = SpeculativeProfiles::new(
+            session.get_default_execution_profile_handle().clone(),
+            config.speculative_execution,
+        );
 
-        let session = builder.build().await?;
+        let inner = CachingSessionBuilder::new(session)
+            .use_cached_result_metadata(true)
+            .build();
 
         Ok(Self {
-            inner: CachingSessionBuilder::new(session)
-                .use_cached_result_metadata(true)
-                .build()
-                .into(),
-            app_name: app_name.into(),
-            app_instance,
-            app_version: app_version.into(),
+            prepared_texts: PreparedTexts::new(inner.get_max_capacity()).into(),
+            inner: inner.into(),
+            app_name: config.app_name.into(),
+            app_instance: config.app_instance,
+            app_version: config.app_version.into(),
+            replication_factor: config.replication_factor,
+            implementation: config.implementation.map(Into::into),
+            credentials,
+            query_options,
+            speculative_profiles: speculative_profiles.into(),
+            token_signer: config.token_signer,
+            metrics: config.metrics,
+            slow_query_log: config.slow_query_log,
+            consistency: config.consistency,
+            redaction: config.redaction,
+            repair_migrations: config.repair_migrations,
+            migration_lock: config.migration_lock,
         })
     }
 
+    /// Returns an instance sharing the same session that uses `options` for values that are not
+    /// set per query.
+    #[must_use]
+    pub fn with_query_options(&self, options: QueryOptions) -> Self {
+        Self {
+            query_options: options.or(self.query_options),
+            ..self.clone()
+        }
+    }
+
+    /// Replaces the credentials used when new connections are opened. Established connections
+    /// stay authenticated with the credentials they were opened with.
+    pub fn rotate_credentials(&self, credentials: Credentials) {
+        debug!(username = credentials.username(), "Rotate credentials");
+        self.credentials.rotate(credentials);
+    }
+
+    /// Connects with the configuration in `path`, overridden by environment variables
+    /// prefixed with [`ENV_PREFIX`].
+    pub async fn from_toml(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Self::connect(InstanceConfig::from_toml(path)?.with_env(ENV_PREFIX)?).await
+    }
+
     pub async fn set_keyspace(&self) -> Result<(), LoadError> {
         Ok(self
             .inner
@@ -134,7 +393,24 @@ impl Instance {
             .await?)
     }
 
-    pub async fn migrate(&self, file: &str, cql: &str) -> Result<(), MigrationError> {
+    /// Applies the statements of `file` that haven't been applied yet, returns how many were
+    /// applied. Waits for other runners of the application to finish first.
+    pub async fn migrate(&self, file: &str, cql: &str) -> Result<usize, MigrationError> {
+        let lease = Lease::acquire(self).await?;
+        let applied = lease.hold(self.migrate_file(file, cql, &lease)).await;
+        let released = lease.release().await;
+
+        let applied = applied?;
+        released?;
+        Ok(applied)
+    }
+
+    async fn migrate_file(
+        &self,
+        file: &str,
+        cql: &str,
+        lease: &Lease<'_>,
+    ) -> Result<usize, MigrationError> {
         let meta_keyspace = meta_keyspace();
         let last_index = self
             .inner
@@ -153,27 +429,57 @@ impl Instance {
 
         debug!(file_name = file, last_index, "Run migration");
 
-        iter(
-            cql.split(';')
-                .map(str::trim)
-                .filter(|statement| !statement.is_empty())
-                .enumerate()
-                .map(|(idx, statement)| (idx.try_into().unwrap(), statement))
-                .skip_while(|(idx, _)| last_index.is_some_and(|last_index| *idx <= last_index)),
-        )
-        .then(async |(idx, statement)| {
-            debug!(index = idx, statement = statement, "Executing statement");
-            self.inner
-                .get_session()
-                .query_unpaged(statement, ())
-                .await
-                .map_err(|err| MigrationError::Migration {
-                    file: (file).into(),
-                    index: idx,
-                    error: err,
-                    statement: statement.to_string(),
-                })?;
+        let statements = script::split(cql).map_err(|error| MigrationError::Script {
+            file: file.into(),
+            error,
+        })?;
+
+        if let Some(last_index) = last_index {
+            self.verify_checksums(file, &statements, last_index).await?;
+        }
+
+        let mut applied = 0;
+        for (idx, statement) in statements
+            .into_iter()
+            .zip(0..)
+            .map(|(statement, idx)| (idx, statement))
+            .skip_while(|(idx, _)| last_index.is_some_and(|last_index| *idx <= last_index))
+        {
+            debug!(
+                index = idx,
+                statement = &*self.redaction.redact(statement),
+                "Executing statement"
+            );
+            self.measure(statement, async {
+                let result = self
+                    .inner
+                    .get_session()
+                    .query_unpaged(statement, ())
+                    .await
+                    .map_err(|err| match err {
+                        scylla::errors::ExecutionError::SchemaAgreementError(error) => {
+                            MigrationError::SchemaAgreement {
+                                file: file.into(),
+                                index: idx,
+                                error,
+                            }
+                        }
+                        err => MigrationError::Migration {
+                            file: file.into(),
+                            index: idx,
+                            error: self.redaction.execution_error(err),
+                            statement: self.redaction.redact(statement).into_owned(),
+                        },
+                    })?;
+                let mut execution = Execution::with_values(0, self.consistency);
+                execution.page(result.tracing_id());
+                Ok::<_, MigrationError>(((), execution))
+            })
+            .await?;
 
+            // Confirms no other runner took the lease over while the statement ran.
+            lease.renew().await?;
+            self.store_checksum(file, idx, &checksum(statement)).await?;
             self.inner
                 .get_session()
                 .query_unpaged(
@@ -185,12 +491,133 @@ impl Instance {
                     (self.app_instance, self.app_name.as_ref(), file, &idx),
                 )
                 .await?;
-            Ok::<_, MigrationError>(())
-        })
-        .try_collect::<()>()
-        .await?;
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+
+    /// Compares the statements up to `last_index` with the checksums recorded when they were
+    /// applied. Statements applied before checksums were recorded get the checksum of their
+    /// current text.
+    async fn verify_checksums(
+        &self,
+        file: &str,
+        statements: &[&str],
+        last_index: i32,
+    ) -> Result<(), MigrationError> {
+        let meta_keyspace = meta_keyspace();
+        let stored = self
+            .inner
+            .get_session()
+            .query_unpaged(
+                format!(
+                    r#"select "index", checksum from {meta_keyspace}.migration_checksum
+                         where app_instance = ? and app_name = ? and file_name = ?"#
+                ),
+                (self.app_instance, self.app_name.as_ref(), file),
+            )
+            .await?
+            .into_rows_result()?
+            .rows::<(i32, Vec<u8>)>()?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        for index in 0..=last_index {
+            let statement = usize::try_from(index)
+                .ok()
+                .and_then(|index| statements.get(index));
+            let checksum = statement.map(|statement| checksum(statement));
+
+            match (stored.get(&index), checksum) {
+                (Some(stored), Some(checksum)) if *stored == checksum => {}
+                (None, Some(checksum)) => self.store_checksum(file, index, &checksum).await?,
+                _ if !self.repair_migrations => {
+                    return Err(MigrationError::ChecksumMismatch {
+                        file: file.into(),
+                        index,
+                    });
+                }
+                (_, Some(checksum)) => {
+                    warn!(
+                        file_name = file,
+                        index, "Repair checksum of changed migration"
+                    );
+                    self.store_checksum(file, index, &checksum).await?;
+                }
+                (_, None) => warn!(file_name = file, index, "Applied migration was removed"),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn store_checksum(
+        &self,
+        file: &str,
+        index: i32,
+        checksum: &[u8],
+    ) -> Result<(), MigrationError> {
+        let meta_keyspace = meta_keyspace();
+        self.inner
+            .get_session()
+            .query_unpaged(
+                format!(
+                    r#"insert into {meta_keyspace}.migration_checksum
+                 (app_instance, app_name, file_name, "index", checksum)
+                  values (?, ?, ?, ?, ?)"#
+                ),
+                (
+                    self.app_instance,
+                    self.app_name.as_ref(),
+                    file,
+                    index,
+                    checksum,
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Applies `migrations` in order, stopping at the first statement that fails. Waits for
+    /// other runners of the application to finish first.
+    pub async fn migrate_all(
+        &self,
+        migrations: Migrations,
+    ) -> Result<MigrationSummary, MigrationError> {
+        let lease = Lease::acquire(self).await?;
+        let summary = lease.hold(self.migrate_files(migrations, &lease)).await;
+        let released = lease.release().await;
+
+        let summary = summary?;
+        released?;
 
-        Ok::<_, MigrationError>(())
+        debug!(
+            applied = summary.applied.len(),
+            statements = summary.statements(),
+            unchanged = summary.unchanged,
+            "Migrations done"
+        );
+        Ok(summary)
+    }
+
+    async fn migrate_files(
+        &self,
+        migrations: Migrations,
+        lease: &Lease<'_>,
+    ) -> Result<MigrationSummary, MigrationError> {
+        let mut summary = MigrationSummary::default();
+
+        for migration in migrations.files() {
+            match self
+                .migrate_file(migration.file, migration.cql, lease)
+                .await?
+            {
+                0 => summary.unchanged += 1,
+                applied => summary.applied.push((migration.file, applied)),
+            }
+        }
+
+        Ok(summary)
     }
 
     pub async fn query(
@@ -198,53 +625,532 @@ impl Instance {
         query: impl Into<Statement>,
         data: impl SerializeRow + Send + Sync,
     ) -> Result<QueryResult, Error> {
-        let query = query.into();
+        self.query_with(query, data, QueryOptions::new()).await
+    }
 
-        Ok(self
-            .inner
-            .execute_single_page(query.clone(), data, PagingState::start())
+    pub async fn query_with(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+        options: QueryOptions,
+    ) -> Result<QueryResult, Error> {
+        let (query, retry) = self.statement(query, options);
+
+        self.measure(&query.contents, async {
+            let (prepared, mut execution) = self
+                .prepare_execution(&query)
+                .await
+                .map_err(|err| Error::Execution(err.into()))?;
+            let (result, _) = self
+                .execute_single_page(&prepared, &data, PagingState::start(), retry)
+                .await?;
+            execution.page(result.tracing_id());
+            Ok((result, execution))
+        })
+        .await
+    }
+
+    /// Fetches a single page of at most `page_size` rows, starting at `cursor` or at the first
+    /// row when `None`.
+    pub async fn query_page<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+        cursor: Option<Cursor>,
+        page_size: NonZeroU32,
+    ) -> Result<Page<T>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        let (query, retry) = self.statement(query, QueryOptions::new().page_size(page_size));
+
+        self.fetch_page(query, data, cursor, retry).await
+    }
+
+    /// Same as [`Instance::query_page`] but the returned token is signed with the key from
+    /// [`InstanceConfig::continuation_key`] and bound to the statement text and the bound
+    /// values. Tokens that were tampered with, or belong to another statement or other values,
+    /// fail with [`Error::TokenMismatch`].
+    pub async fn query_page_signed<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+        token: Option<&ContinuationToken>,
+        page_size: NonZeroU32,
+    ) -> Result<Page<T, ContinuationToken>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        let signer = self
+            .token_signer
+            .as_deref()
+            .ok_or(Error::ContinuationKeyMissing)?;
+        let (query, retry) = self.statement(query, QueryOptions::new().page_size(page_size));
+        let values = self.serialized_values(&query, &data).await?;
+        let cursor = token
+            .map(|token| signer.verify(token, &query.contents, &values))
+            .transpose()?;
+        let statement = query.contents.clone();
+
+        let page = self.fetch_page(query, data, cursor, retry).await?;
+
+        Ok(Page {
+            rows: page.rows,
+            next: page
+                .next
+                .map(|cursor| signer.sign(&cursor, &statement, &values)),
+        })
+    }
+
+    async fn fetch_page<T>(
+        &self,
+        query: Statement,
+        data: impl SerializeRow + Send + Sync,
+        cursor: Option<Cursor>,
+        retry: Option<RetryPolicy>,
+    ) -> Result<Page<T>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        let (result, paging_state) = self
+            .measure(&query.contents, async {
+                let (prepared, mut execution) = self
+                    .prepare_execution(&query)
+                    .await
+                    .map_err(|err| Error::Execution(err.into()))?;
+                let page = self
+                    .execute_single_page(
+                        &prepared,
+                        &data,
+                        cursor.map_or_else(PagingState::start, |cursor| cursor.paging_state()),
+                        retry,
+                    )
+                    .await?;
+                execution.page(page.0.tracing_id());
+                Ok::<_, Error>((page, execution))
+            })
+            .await?;
+
+        Ok(Page {
+            rows: result
+                .into_rows_result()?
+                .rows::<T>()?
+                .collect::<Result<_, _>>()?,
+            next: Cursor::from_response(paging_state),
+        })
+    }
+
+    /// Values bound by `data` as they are sent with the statement, so filters on clustering
+    /// and indexed columns are covered as well as the partition key.
+    async fn serialized_values(
+        &self,
+        query: &Statement,
+        data: &(impl SerializeRow + Sync),
+    ) -> Result<Vec<u8>, Error> {
+        let prepared = self
+            .prepare(query)
             .await
-            .map_err(|err| {
-                error!(
-                    query = &query.contents,
-                    error = &err as &dyn std::error::Error
-                );
-                Error::Execution(err)
-            })?
-            .0)
+            .map_err(|err| Error::Execution(err.into()))?;
+
+        let mut values = Vec::new();
+        data.serialize(
+            &RowSerializationContext::from_specs(prepared.get_variable_col_specs().as_slice()),
+            &mut RowWriter::new(&mut values),
+        )
+        .map_err(|err| Error::Execution(BadQuery::SerializationError(err).into()))?;
+        Ok(values)
+    }
+
+    async fn execute_single_page(
+        &self,
+        prepared: &PreparedStatement,
+        data: &(impl SerializeRow + Sync),
+        paging_state: PagingState,
+        retry: Option<RetryPolicy>,
+    ) -> Result<(QueryResult, PagingStateResponse), Error> {
+        let (result, paging_state) = retry::retry(retry, || async {
+            self.inner
+                .get_session()
+                .execute_single_page(prepared, data, paging_state.clone())
+                .await
+                .map_err(|err| {
+                    let err = self.redaction.execution_error(err);
+                    error!(
+                        query = &*self.redaction.redact(prepared.get_statement()),
+                        error = &err as &dyn std::error::Error
+                    );
+                    Error::Execution(err)
+                })
+        })
+        .await?;
+
+        if let Some(metrics) = &self.metrics {
+            let statement = self.redaction.redact(prepared.get_statement());
+            metrics.page(&statement);
+            if let Ok(rows) = result.clone().into_rows_result() {
+                metrics.rows(&statement, rows.rows_num());
+            }
+        }
+
+        Ok((result, paging_state))
     }
 
+    /// Metrics and the slow query log only cover the request for the first page. Counting the
+    /// rows and pages fetched by the returned pager is out of scope, as the pager is the driver's
+    /// own type, [`Instance::query_stream`] counts them.
     pub async fn query_iter(
         &self,
         query: impl Into<Statement>,
         data: impl SerializeRow + Send + Sync,
     ) -> Result<QueryPager, Error> {
-        let query = query.into();
+        self.query_iter_with(query, data, QueryOptions::new()).await
+    }
 
-        self.inner
-            .execute_iter(query.clone(), data)
-            .await
-            .map_err(|err| {
-                error!(
-                    query = &query.contents,
-                    error = &err as &dyn std::error::Error
-                );
-                Error::PagerExecution(err)
+    pub async fn query_iter_with(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+        options: QueryOptions,
+    ) -> Result<QueryPager, Error> {
+        let (query, retry) = self.statement(query, options);
+
+        self.measure(&query.contents, async {
+            let (prepared, mut execution) = self
+                .prepare_execution(&query)
+                .await
+                .map_err(|err| Error::PagerExecution(err.into()))?;
+            let pager = retry::retry(retry, || async {
+                self.inner
+                    .get_session()
+                    .execute_iter(prepared.clone(), &data)
+                    .await
+                    .map_err(|err| {
+                        let err = self.redaction.pager_error(err);
+                        error!(
+                            query = &*self.redaction.redact(&query.contents),
+                            error = &err as &dyn std::error::Error
+                        );
+                        Error::PagerExecution(err)
+                    })
             })
+            .await?;
+
+            execution.page(pager.tracing_ids().first().copied());
+            Ok((pager, execution))
+        })
+        .await
     }
 
-    #[instrument(skip(self), err)]
-    pub async fn setup(
+    pub async fn query_one<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+    ) -> Result<T, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        self.query_optional(query, data).await?.ok_or(Error::NoRows)
+    }
+
+    pub async fn query_optional<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+    ) -> Result<Option<T>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        let (query, retry) = self.statement(query, QueryOptions::new());
+
+        // Pages can be empty or short while more rows follow, so pages are fetched until a
+        // second row shows up or the result ends.
+        self.measure(&query.contents, async {
+            let (prepared, mut execution) = self
+                .prepare_execution(&query)
+                .await
+                .map_err(|err| Error::Execution(err.into()))?;
+            let mut paging_state = PagingState::start();
+            let mut row = None;
+            let mut rows = 0;
+
+            loop {
+                let (result, response) = self
+                    .execute_single_page(&prepared, &data, paging_state, retry)
+                    .await?;
+                execution.page(result.tracing_id());
+                let result = result.into_rows_result()?;
+
+                rows += result.rows_num();
+                if rows > 1 {
+                    return Err(Error::TooManyRows(rows));
+                }
+                if row.is_none() {
+                    row = result.rows::<T>()?.next().transpose()?;
+                }
+
+                match response.into_paging_control_flow() {
+                    ControlFlow::Break(()) => return Ok((row, execution)),
+                    ControlFlow::Continue(state) => paging_state = state,
+                }
+            }
+        })
+        .await
+    }
+
+    /// Executes a lightweight transaction such as `INSERT ... IF NOT EXISTS` or `UPDATE ... IF`,
+    /// decoding the `[applied]` flag and the current values when the condition didn't hold.
+    pub async fn execute_conditional<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+    ) -> Result<Conditional<T>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata>,
+    {
+        self.query_one(query, data).await
+    }
+
+    /// Fetches all pages, use [`Instance::query_iter`] for results that don't fit in memory.
+    pub async fn query_all<T>(
+        &self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata> + 'static,
+    {
+        let (query, retry) = self.statement(query, QueryOptions::new());
+
+        self.measure(&query.contents, async {
+            let (prepared, mut execution) = self
+                .prepare_execution(&query)
+                .await
+                .map_err(|err| Error::Execution(err.into()))?;
+            let mut paging_state = PagingState::start();
+            let mut rows = Vec::new();
+
+            loop {
+                let (result, response) = self
+                    .execute_single_page(&prepared, &data, paging_state, retry)
+                    .await?;
+                execution.page(result.tracing_id());
+                rows.extend(
+                    result
+                        .into_rows_result()?
+                        .rows::<T>()?
+                        .collect::<Result<Vec<_>, _>>()?,
+                );
+
+                match response.into_paging_control_flow() {
+                    ControlFlow::Break(()) => return Ok((rows, execution)),
+                    ControlFlow::Continue(state) => paging_state = state,
+                }
+            }
+        })
+        .await
+    }
+
+    /// Rows are fetched page by page and decoded as the stream is polled, ending after `limit`
+    /// rows when set. Errors from executing the query are returned as the first item.
+    ///
+    /// Rows and pages are counted for every page fetched. The latency, errors and the slow query
+    /// log cover preparing the statement and fetching the first page.
+    pub fn query_stream<'a, T>(
+        &'a self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync + 'a,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = Result<T, Error>> + Send + 'a
+    where
+        T: for<'frame, 'metadata> DeserializeRow<'frame, 'metadata> + Send + 'static,
+    {
+        let (query, retry) = self.statement(query, QueryOptions::new());
+
+        try_unfold(
+            (query, data, None, Some(PagingState::start())),
+            move |(query, data, prepared, paging_state)| async move {
+                let Some(paging_state) = paging_state else {
+                    return Ok(None);
+                };
+
+                let (prepared, (result, response)) = match prepared {
+                    Some(prepared) => {
+                        let page = self
+                            .execute_single_page(&prepared, &data, paging_state, retry)
+                            .await?;
+                        (prepared, page)
+                    }
+                    None => {
+                        self.measure(&query.contents, async {
+                            let (prepared, mut execution) = self
+                                .prepare_execution(&query)
+                                .await
+                                .map_err(|err| Error::Execution(err.into()))?;
+                            let page = self
+                                .execute_single_page(&prepared, &data, paging_state, retry)
+                                .await?;
+                            execution.page(page.0.tracing_id());
+                            Ok::<_, Error>(((prepared, page), execution))
+                        })
+                        .await?
+                    }
+                };
+
+                let rows = result
+                    .into_rows_result()?
+                    .rows::<T>()?
+                    .collect::<Result<Vec<_>, _>>()?;
+                let next = match response.into_paging_control_flow() {
+                    ControlFlow::Break(()) => None,
+                    ControlFlow::Continue(state) => Some(state),
+                };
+
+                Ok::<_, Error>(Some((rows, (query, data, Some(prepared), next))))
+            },
+        )
+        .map_ok(|rows| iter(rows.into_iter().map(Ok)))
+        .try_flatten()
+        .take(limit.unwrap_or(usize::MAX))
+    }
+
+    /// Prepares `queries` up front so invalid statements fail at startup instead of on first
+    /// use. Stops at the first statement that can't be prepared.
+    pub async fn prepare_all<const N: usize>(
+        &self,
+        queries: &[&str; N],
+    ) -> Result<[Prepared; N], PrepareError> {
+        let prepared = try_join_all(queries.iter().map(async |query| {
+            // The options are applied again whenever the statement is executed, so it's kept
+            // without them.
+            let statement = Statement::new(*query);
+            let (applied, _) = self.statement(statement.clone(), QueryOptions::new());
+            let prepared = self.prepare(&applied).await.map_err(|error| {
+                let query = self.redaction.redact(&statement.contents).into_owned();
+                error!(query, error = &error as &dyn std::error::Error);
+                PrepareError { query, error }
+            })?;
+            Ok(Prepared::new(statement, prepared))
+        }))
+        .await?;
+
+        debug!(statements = N, "Prepared statements");
+
+        Ok(prepared
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("one statement is prepared per query")))
+    }
+
+    /// Reads the trace of a request executed with [`QueryOptions::tracing`], `trace_id` is the
+    /// tracing id of its result. Waits for the trace to be complete as it's written
+    /// asynchronously by the nodes.
+    pub async fn fetch_trace(&self, trace_id: Uuid) -> Result<Trace, Error> {
+        let mut trace = Trace::new(
+            trace_id,
+            self.inner.get_session().get_tracing_info(&trace_id).await?,
+        );
+
+        if let Some(query) = trace.parameters.get_mut("query") {
+            *query = self.redaction.redact(query).into_owned();
+        }
+        Ok(trace)
+    }
+
+    /// Starts a batch of `batch_type`, statements are added with [`Batch::statement`].
+    pub const fn batch(&self, batch_type: BatchType) -> Batch<'_> {
+        Batch::new(self, batch_type)
+    }
+
+    /// Statement with `options` applied and the retry policy the instance waits between retries
+    /// for.
+    fn statement(
+        &self,
+        query: impl Into<Statement>,
+        options: QueryOptions,
+    ) -> (Statement, Option<RetryPolicy>) {
+        let mut query = query.into();
+        let options = options.or(self.query_options);
+        options.apply(&mut query, &self.speculative_profiles);
+        let retry = options.backoff(query.get_is_idempotent());
+        (query, retry)
+    }
+
+    /// Prepares `query` through the statement cache, recording whether it was already cached.
+    async fn prepare(
+        &self,
+        query: &Statement,
+    ) -> Result<PreparedStatement, scylla::errors::PrepareError> {
+        let cache_hit = self.metrics.is_some() && self.prepared_texts.contains(&query.contents);
+        let prepared = self
+            .inner
+            .add_prepared_statement(query)
+            .await
+            .map_err(|err| self.redaction.prepare_error(err))?;
+
+        if let Some(metrics) = &self.metrics {
+            self.prepared_texts.insert(&query.contents);
+            metrics.prepared(&self.redaction.redact(&query.contents), cache_hit);
+        }
+        Ok(prepared)
+    }
+
+    /// Prepares `query` and starts describing its execution for the slow query log, enabling
+    /// tracing on the returned copy when the execution is sampled.
+    async fn prepare_execution(
         &self,
-        replication_factor: usize,
-        implementation: Option<&str>,
-    ) -> Result<(), SetupError> {
+        query: &Statement,
+    ) -> Result<(PreparedStatement, Execution), scylla::errors::PrepareError> {
+        let mut prepared = self.prepare(query).await?;
+        if self.slow_query_log.is_some_and(SlowQueryLog::sample) {
+            prepared.set_tracing(true);
+        }
+        let execution = Execution::new(&prepared, self.consistency);
+        Ok((prepared, execution))
+    }
+
+    /// Records the latency of `execute` and the kind of error it fails with, and logs it when it
+    /// was slow.
+    async fn measure<T, E>(
+        &self,
+        statement: &str,
+        execute: impl Future<Output = Result<(T, Execution), E>>,
+    ) -> Result<T, E>
+    where
+        E: Classify + std::error::Error + 'static,
+    {
+        if self.metrics.is_none() && self.slow_query_log.is_none() {
+            return execute.await.map(|(value, _)| value);
+        }
+
+        let start = Instant::now();
+        let result = execute.await;
+        let elapsed = start.elapsed();
+        let statement = self.redaction.redact(statement);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.latency(&statement, elapsed);
+            if let Err(err) = &result {
+                metrics.error(&statement, err.kind());
+            }
+        }
+        if let Some(log) = self.slow_query_log {
+            log.log(
+                &statement,
+                elapsed,
+                result.as_ref().map(|(_, execution)| execution),
+            );
+        }
+
+        result.map(|(value, _)| value)
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn setup(&self) -> Result<(), SetupError> {
         create_structure(
             &self.inner,
             self.app_instance,
             &self.app_name,
-            implementation,
-            replication_factor,
+            self.implementation.as_deref(),
+            self.replication_factor,
         )
         .await?;
 
@@ -258,8 +1164,7 @@ impl Instance {
             )
             .await?
             .into_rows_result()?
-            .single_row::<(Option<Vec<(String, CqlTimestamp)>>,)>()
-            .unwrap()
+            .single_row::<(Option<Vec<(String, CqlTimestamp)>>,)>()?
             .0
             .map(|v| v.into_iter().any(|(v, _)| *v == *self.app_version));
 
@@ -280,7 +1185,7 @@ impl Instance {
             .execute_unpaged(
                 format!(
                     r#"drop keyspace if exists "{}""#,
-                    &data_keyspace(self.app_instance, &self.app_name)
+                    data_keyspace(self.app_instance, &self.app_name)
                 ),
                 (),
             )
@@ -292,6 +1197,58 @@ impl Instance {
     }
 }
 
+async fn build_session(
+    builder: &SessionBuilder,
+    retry: Option<ConnectRetry>,
+) -> Result<Session, LoadError> {
+    let Some(retry) = retry else {
+        return Ok(Box::pin(builder.build()).await?);
+    };
+
+    let started = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let error = match Box::pin(builder.build()).await {
+            Ok(session) => return Ok(session),
+            Err(
+                error @ (NewSessionError::FailedToResolveAnyHostname(_)
+                | NewSessionError::MetadataError(_)),
+            ) => error,
+            Err(error) => return Err(error.into()),
+        };
+
+        let elapsed = started.elapsed();
+        let delay = retry
+            .delay(attempt)
+            .min(retry.max_wait.saturating_sub(elapsed));
+
+        if delay.is_zero() {
+            error!(
+                attempt,
+                ?elapsed,
+                error = &error as &dyn std::error::Error,
+                "Cluster unreachable, giving up"
+            );
+            return Err(LoadError::Unreachable {
+                attempts: attempt,
+                elapsed,
+                error,
+            });
+        }
+
+        warn!(
+            attempt,
+            ?delay,
+            error = &error as &dyn std::error::Error,
+            "Cluster unreachable, retrying"
+        );
+        sleep(delay).await;
+    }
+}
+
 #[instrument(skip(session), err)]
 async fn create_structure(
     session: &CachingSession,
@@ -303,11 +1260,11 @@ async fn create_structure(
     let meta_keyspace = meta_keyspace();
     let data_keyspace = data_keyspace(instance, name);
 
-    let mut tt = TinyTemplate::new();
-    tt.add_template("structure", include_str!("../structure.cql"))
-        .unwrap();
+    let structure = {
+        let mut tt = TinyTemplate::new();
+        tt.add_template("structure", include_str!("../structure.cql"))
+            .unwrap();
 
-    iter(
         tt.render(
             "structure",
             &HashMap::from([
@@ -317,18 +1274,24 @@ async fn create_structure(
             ]),
         )
         .unwrap()
-        .split(';')
-        .map(str::trim)
-        .filter(|statement| !statement.is_empty())
-        .enumerate(),
-    )
-    .then(async |(idx, statement)| {
-        debug!(index = idx, statement = statement, "Executing statement");
-        session.execute_unpaged(statement, ()).await?;
-        Ok::<_, SetupError>(())
-    })
-    .try_collect::<()>()
-    .await?;
+    };
+
+    iter(script::split(&structure)?.into_iter().enumerate())
+        .then(async |(idx, statement)| {
+            debug!(index = idx, statement = statement, "Executing statement");
+            session
+                .execute_unpaged(statement, ())
+                .await
+                .map_err(|err| match err {
+                    scylla::errors::ExecutionError::SchemaAgreementError(err) => {
+                        SetupError::SchemaAgreement(err)
+                    }
+                    err => SetupError::Execution(err),
+                })?;
+            Ok::<_, SetupError>(())
+        })
+        .try_collect::<()>()
+        .await?;
 
     session
         .execute_unpaged(
@@ -346,6 +1309,10 @@ fn data_keyspace(instance: Uuid, name: &str) -> String {
     format!("{}_{}", name.replace('-', "_"), encode(instance.as_u128()))
 }
 
+fn checksum(statement: &str) -> Vec<u8> {
+    Sha256::digest(statement.as_bytes()).to_vec()
+}
+
 const fn meta_keyspace() -> &'static str {
     "app_metadata"
 }
@@ -0,0 +1,151 @@
+use crate::scylla::{Error, Instance, QueryOptions, SlowQueryLog, retry, slow_query::Execution};
+use scylla::{
+    errors::{BadQuery, ExecutionError},
+    response::query_result::QueryResult,
+    serialize::{
+        row::{RowSerializationContext, SerializeRow},
+        writers::RowWriter,
+    },
+    statement::{
+        Statement,
+        batch::{Batch as ScyllaBatch, BatchType},
+        prepared::PreparedStatement,
+    },
+};
+use tracing::{debug, error};
+
+/// Statements executed together in a single batch, created with [`Instance::batch`].
+///
+/// Every statement is prepared through the prepared statement cache before the batch is sent.
+/// Failures to prepare a statement or to serialize its values are reported with the position of
+/// the statement in the batch.
+#[must_use]
+pub struct Batch<'a> {
+    instance: &'a Instance,
+    kind: BatchType,
+    options: QueryOptions,
+    statements: Vec<(Statement, Box<dyn SerializeRow + Send + Sync + 'a>)>,
+}
+
+impl<'a> Batch<'a> {
+    pub(super) const fn new(instance: &'a Instance, batch_type: BatchType) -> Self {
+        Self {
+            instance,
+            kind: batch_type,
+            options: QueryOptions::new(),
+            statements: Vec::new(),
+        }
+    }
+
+    pub fn statement(
+        mut self,
+        query: impl Into<Statement>,
+        data: impl SerializeRow + Send + Sync + 'a,
+    ) -> Self {
+        self.statements.push((query.into(), Box::new(data)));
+        self
+    }
+
+    /// Options for the whole batch, the page size is ignored.
+    pub const fn options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn execute(self) -> Result<QueryResult, Error> {
+        let mut batch = ScyllaBatch::new(self.kind);
+        let options = self.options.or(self.instance.query_options);
+        options.apply_batch(&mut batch, &self.instance.speculative_profiles);
+        let retry = options.backoff(batch.get_is_idempotent());
+        if self
+            .instance
+            .slow_query_log
+            .is_some_and(SlowQueryLog::sample)
+        {
+            batch.set_tracing(true);
+        }
+
+        let contents = contents(self.kind, &self.statements);
+        let mut values = Vec::with_capacity(self.statements.len());
+        let mut bound_values = 0;
+        for (index, (statement, data)) in self.statements.into_iter().enumerate() {
+            let prepared = prepare(self.instance, &statement, data.as_ref())
+                .await
+                .map_err(|error| Error::Batch {
+                    index,
+                    statement: self
+                        .instance
+                        .redaction
+                        .redact(&statement.contents)
+                        .into_owned(),
+                    error,
+                })?;
+
+            bound_values += prepared.get_variable_col_specs().len();
+            batch.append_statement(prepared);
+            values.push(data);
+        }
+
+        debug!(statements = values.len(), "Executing batch");
+
+        self.instance
+            .measure(&contents, async {
+                let result = retry::retry(retry, || async {
+                    self.instance
+                        .inner
+                        .batch(&batch, &values)
+                        .await
+                        .map_err(|err| {
+                            let err = self.instance.redaction.execution_error(err);
+                            error!(
+                                statements = batch.statements.len(),
+                                error = &err as &dyn std::error::Error
+                            );
+                            Error::Execution(err)
+                        })
+                })
+                .await?;
+
+                let mut execution = Execution::with_values(
+                    bound_values,
+                    batch.get_consistency().unwrap_or(self.instance.consistency),
+                );
+                execution.page(result.tracing_id());
+                Ok((result, execution))
+            })
+            .await
+    }
+}
+
+/// CQL text of the batch, used as its statement in metrics and the slow query log.
+fn contents(kind: BatchType, statements: &[(Statement, impl Sized)]) -> String {
+    let kind = match kind {
+        BatchType::Logged => "",
+        BatchType::Unlogged => "UNLOGGED ",
+        BatchType::Counter => "COUNTER ",
+    };
+
+    statements.iter().fold(
+        format!("BEGIN {kind}BATCH "),
+        |mut contents, (statement, _)| {
+            contents.push_str(&statement.contents);
+            contents.push_str("; ");
+            contents
+        },
+    ) + "APPLY BATCH"
+}
+
+async fn prepare(
+    instance: &Instance,
+    statement: &Statement,
+    data: &(dyn SerializeRow + Send + Sync + '_),
+) -> Result<PreparedStatement, ExecutionError> {
+    let prepared = instance.prepare(statement).await?;
+
+    data.serialize(
+        &RowSerializationContext::from_specs(prepared.get_variable_col_specs().as_slice()),
+        &mut RowWriter::new(&mut Vec::new()),
+    )
+    .map_err(|err| BadQuery::SerializationError(err).into())
+    .map(|()| prepared)
+}
@@ -0,0 +1,291 @@
+use crate::scylla::{
+    ConfigError, CursorError, Error, LoadError, MappingError, MigrationError, PersistError,
+    PrepareError, ScriptError, SetupError, TlsError,
+};
+use scylla::errors::{
+    DbError, ExecutionError, NewSessionError, NextPageError, NextRowError, PagerExecutionError,
+    RequestAttemptError, RequestError, SchemaAgreementError, TracingError, UseKeyspaceError,
+};
+
+/// Broad cause of a failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The coordinator or the client gave up waiting for the replicas.
+    Timeout,
+    /// Not enough replicas or no connection to any node.
+    Unavailable,
+    /// The cluster is shedding load.
+    Overloaded,
+    /// A connection broke while the request was in flight.
+    Connection,
+    /// The statement is invalid or doesn't match the schema.
+    Schema,
+    Other,
+}
+
+impl ErrorKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Unavailable => "unavailable",
+            Self::Overloaded => "overloaded",
+            Self::Connection => "connection",
+            Self::Schema => "schema",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Classification shared by all errors of the crate.
+pub trait Classify {
+    fn kind(&self) -> ErrorKind;
+
+    fn is_timeout(&self) -> bool {
+        self.kind() == ErrorKind::Timeout
+    }
+
+    fn is_unavailable(&self) -> bool {
+        self.kind() == ErrorKind::Unavailable
+    }
+
+    fn is_overloaded(&self) -> bool {
+        self.kind() == ErrorKind::Overloaded
+    }
+
+    fn is_schema_error(&self) -> bool {
+        self.kind() == ErrorKind::Schema
+    }
+
+    /// Transient failures where executing the same request again may succeed.
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Timeout
+                | ErrorKind::Unavailable
+                | ErrorKind::Overloaded
+                | ErrorKind::Connection
+        )
+    }
+}
+
+impl Classify for PersistError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Prepare(err) => err.kind(),
+            Self::Load(err) => err.kind(),
+            Self::Setup(err) => err.kind(),
+            Self::Migration(err) => err.kind(),
+            Self::Query(err) => err.kind(),
+            Self::Mapping(_) | Self::Config(_) | Self::Tls(_) | Self::Cursor(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for MappingError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl Classify for PrepareError {
+    fn kind(&self) -> ErrorKind {
+        prepare(&self.error)
+    }
+}
+
+impl Classify for ScriptError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Schema
+    }
+}
+
+impl Classify for ConfigError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl Classify for TlsError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl Classify for CursorError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl Classify for LoadError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Load(err) => new_session(err),
+            Self::Unreachable { .. } => ErrorKind::Unavailable,
+            Self::KeyspaceSetup(err) => use_keyspace(err),
+            Self::Config(_) | Self::Tls(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for SetupError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Execution(err) => execution(err),
+            Self::Script(err) => err.kind(),
+            Self::SchemaAgreement(err) => schema_agreement(err),
+            Self::Result(_) | Self::Row(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for MigrationError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Migration { error, .. } | Self::Execution(error) => execution(error),
+            Self::Script { error, .. } => error.kind(),
+            Self::SchemaAgreement { error, .. } => schema_agreement(error),
+            Self::ChecksumMismatch { .. }
+            | Self::Locked { .. }
+            | Self::LockLost
+            | Self::VersionQuery(_)
+            | Self::Result(_)
+            | Self::Rows(_)
+            | Self::Deserialization(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl Classify for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PagerExecution(err) => pager_execution(err),
+            Self::Execution(err) | Self::Batch { error: err, .. } => execution(err),
+            Self::NextRow(err) => next_row(err),
+            Self::Trace(err) => tracing(err),
+            Self::TypeCheck(_) => ErrorKind::Schema,
+            Self::RowsResult(_)
+            | Self::RowResult(_)
+            | Self::Rows(_)
+            | Self::Deserialization(_)
+            | Self::NoRows
+            | Self::TooManyRows(_)
+            | Self::TokenMismatch
+            | Self::TokenExpired
+            | Self::ContinuationKeyMissing => ErrorKind::Other,
+        }
+    }
+}
+
+pub(super) const fn execution(err: &ExecutionError) -> ErrorKind {
+    match err {
+        ExecutionError::EmptyPlan | ExecutionError::ConnectionPoolError(_) => {
+            ErrorKind::Unavailable
+        }
+        ExecutionError::PrepareError(err) => prepare(err),
+        ExecutionError::LastAttemptError(err) => attempt(err),
+        ExecutionError::RequestTimeout(_) => ErrorKind::Timeout,
+        ExecutionError::UseKeyspaceError(err) => use_keyspace(err),
+        ExecutionError::SchemaAgreementError(err) => schema_agreement(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn prepare(err: &scylla::errors::PrepareError) -> ErrorKind {
+    match err {
+        scylla::errors::PrepareError::ConnectionPoolError(_) => ErrorKind::Unavailable,
+        scylla::errors::PrepareError::AllAttemptsFailed { first_attempt } => attempt(first_attempt),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn pager_execution(err: &PagerExecutionError) -> ErrorKind {
+    match err {
+        PagerExecutionError::PrepareError(err) => prepare(err),
+        PagerExecutionError::NextPageError(err) => next_page(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn next_row(err: &NextRowError) -> ErrorKind {
+    match err {
+        NextRowError::NextPageError(err) => next_page(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn next_page(err: &NextPageError) -> ErrorKind {
+    match err {
+        NextPageError::RequestFailure(err) => request(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn request(err: &RequestError) -> ErrorKind {
+    match err {
+        RequestError::EmptyPlan | RequestError::ConnectionPoolError(_) => ErrorKind::Unavailable,
+        RequestError::RequestTimeout(_) => ErrorKind::Timeout,
+        RequestError::LastAttemptError(err) => attempt(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn tracing(err: &TracingError) -> ErrorKind {
+    match err {
+        TracingError::ExecutionError(err) => execution(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn new_session(err: &NewSessionError) -> ErrorKind {
+    match err {
+        NewSessionError::FailedToResolveAnyHostname(_) | NewSessionError::MetadataError(_) => {
+            ErrorKind::Unavailable
+        }
+        NewSessionError::UseKeyspaceError(err) => use_keyspace(err),
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn use_keyspace(err: &UseKeyspaceError) -> ErrorKind {
+    match err {
+        UseKeyspaceError::BadKeyspaceName(_) | UseKeyspaceError::KeyspaceNameMismatch { .. } => {
+            ErrorKind::Schema
+        }
+        UseKeyspaceError::RequestError(err) => attempt(err),
+        UseKeyspaceError::RequestTimeout(_) => ErrorKind::Timeout,
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn schema_agreement(err: &SchemaAgreementError) -> ErrorKind {
+    match err {
+        SchemaAgreementError::ConnectionPoolError(_) => ErrorKind::Unavailable,
+        SchemaAgreementError::RequestError(err) => attempt(err),
+        SchemaAgreementError::Timeout(_) => ErrorKind::Timeout,
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn attempt(err: &RequestAttemptError) -> ErrorKind {
+    match err {
+        RequestAttemptError::DbError(err, _) => db(err),
+        RequestAttemptError::UnableToAllocStreamId
+        | RequestAttemptError::BrokenConnectionError(_) => ErrorKind::Connection,
+        _ => ErrorKind::Other,
+    }
+}
+
+const fn db(err: &DbError) -> ErrorKind {
+    match err {
+        DbError::ReadTimeout { .. } | DbError::WriteTimeout { .. } => ErrorKind::Timeout,
+        DbError::Unavailable { .. } | DbError::IsBootstrapping => ErrorKind::Unavailable,
+        DbError::Overloaded | DbError::RateLimitReached { .. } => ErrorKind::Overloaded,
+        DbError::SyntaxError
+        | DbError::Invalid
+        | DbError::AlreadyExists { .. }
+        | DbError::ConfigError => ErrorKind::Schema,
+        _ => ErrorKind::Other,
+    }
+}
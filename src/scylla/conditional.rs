@@ -0,0 +1,75 @@
+use scylla::{
+    cluster::metadata::ColumnType,
+    deserialize::{
+        DeserializationError, TypeCheckError,
+        row::{ColumnIterator, DeserializeRow},
+        value::DeserializeValue,
+    },
+    frame::response::result::{ColumnSpec, NativeType},
+};
+
+const APPLIED: &str = "[applied]";
+
+/// Outcome of a lightweight transaction executed with
+/// [`Instance::execute_conditional`](crate::scylla::Instance::execute_conditional).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conditional<T> {
+    Applied,
+    /// The condition didn't hold. Holds the columns returned after `[applied]`, which are the
+    /// existing row for `IF NOT EXISTS` and the conditioned columns otherwise, or `None` when
+    /// there is no existing row.
+    NotApplied(Option<T>),
+}
+
+impl<T> Conditional<T> {
+    #[must_use]
+    pub const fn is_applied(&self) -> bool {
+        matches!(self, Self::Applied)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Expected a boolean {APPLIED} column first")]
+struct AppliedColumnError;
+
+impl<'frame, 'metadata, T> DeserializeRow<'frame, 'metadata> for Conditional<T>
+where
+    T: DeserializeRow<'frame, 'metadata>,
+{
+    fn type_check(specs: &[ColumnSpec]) -> Result<(), TypeCheckError> {
+        match specs.split_first() {
+            Some((applied, rest))
+                if applied.name() == APPLIED
+                    && *applied.typ() == ColumnType::Native(NativeType::Boolean) =>
+            {
+                if rest.is_empty() {
+                    Ok(())
+                } else {
+                    T::type_check(rest)
+                }
+            }
+            _ => Err(TypeCheckError::new(AppliedColumnError)),
+        }
+    }
+
+    fn deserialize(
+        mut row: ColumnIterator<'frame, 'metadata>,
+    ) -> Result<Self, DeserializationError> {
+        let applied = row
+            .next()
+            .ok_or_else(|| DeserializationError::new(AppliedColumnError))??;
+
+        if bool::deserialize(applied.spec.typ(), applied.slice)? {
+            return Ok(Self::Applied);
+        }
+
+        if row
+            .clone()
+            .all(|column| column.is_ok_and(|column| column.slice.is_none()))
+        {
+            return Ok(Self::NotApplied(None));
+        }
+
+        T::deserialize(row).map(|row| Self::NotApplied(Some(row)))
+    }
+}
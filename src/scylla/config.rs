@@ -0,0 +1,841 @@
+mod connect_retry;
+mod credentials;
+mod load_balancing;
+mod source;
+mod tls;
+
+use crate::scylla::{
+    ConfigError, MetricsRecorder, MigrationLock, QueryOptions, Redaction, RetryPolicy,
+    SlowQueryLog, SpeculativeExecution, TlsError, continuation::TokenSigner,
+};
+pub use connect_retry::ConnectRetry;
+pub use credentials::Credentials;
+pub use credentials::CredentialsProvider;
+pub use load_balancing::LoadBalancing;
+use scylla::{
+    client::{PoolSize, execution_profile::ExecutionProfile, session_builder::SessionBuilder},
+    statement::Consistency,
+};
+use secrecy::SecretVec;
+use source::Source;
+use std::{fs, num::NonZeroUsize, path::Path, str::FromStr, sync::Arc, time::Duration};
+pub use tls::TlsConfig;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    #[default]
+    Lz4,
+    Snappy,
+}
+
+impl From<Compression> for Option<scylla::client::Compression> {
+    fn from(val: Compression) -> Self {
+        match val {
+            Compression::None => None,
+            Compression::Lz4 => Some(scylla::client::Compression::Lz4),
+            Compression::Snappy => Some(scylla::client::Compression::Snappy),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "lz4" => Ok(Self::Lz4),
+            "snappy" => Ok(Self::Snappy),
+            value => Err(format!("unknown compression '{value}'")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InstanceConfig {
+    pub(crate) app_instance: Uuid,
+    pub(crate) app_name: String,
+    pub(crate) app_version: String,
+    pub(crate) nodes: Vec<String>,
+    pub(crate) replication_factor: usize,
+    pub(crate) implementation: Option<String>,
+    pub(crate) credentials: Option<Credentials>,
+    pub(crate) tls: Option<TlsConfig>,
+    pub(crate) connection_timeout: Duration,
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) schema_agreement_timeout: Duration,
+    pub(crate) compression: Compression,
+    pub(crate) keepalive_interval: Option<Duration>,
+    pub(crate) keepalive_timeout: Option<Duration>,
+    pub(crate) tcp_keepalive_interval: Option<Duration>,
+    pub(crate) pool_size_per_shard: Option<NonZeroUsize>,
+    pub(crate) load_balancing: LoadBalancing,
+    pub(crate) consistency: Consistency,
+    pub(crate) connect_retry: Option<ConnectRetry>,
+    pub(crate) token_signer: Option<Arc<TokenSigner>>,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) speculative_execution: Option<SpeculativeExecution>,
+    pub(crate) metrics: Option<Arc<dyn MetricsRecorder>>,
+    pub(crate) slow_query_log: Option<SlowQueryLog>,
+    pub(crate) redaction: Redaction,
+    pub(crate) repair_migrations: bool,
+    pub(crate) migration_lock: MigrationLock,
+}
+
+impl InstanceConfig {
+    pub fn new(
+        app_instance: Uuid,
+        app_name: impl Into<String>,
+        app_version: impl Into<String>,
+        nodes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            app_instance,
+            app_name: app_name.into(),
+            app_version: app_version.into(),
+            nodes: nodes.into_iter().map(Into::into).collect(),
+            replication_factor: 1,
+            implementation: None,
+            credentials: None,
+            tls: None,
+            connection_timeout: Duration::from_secs(30),
+            request_timeout: Some(Duration::from_secs(30)),
+            schema_agreement_timeout: Duration::from_mins(1),
+            compression: Compression::Lz4,
+            keepalive_interval: None,
+            keepalive_timeout: None,
+            tcp_keepalive_interval: None,
+            pool_size_per_shard: None,
+            load_balancing: LoadBalancing::new(),
+            consistency: Consistency::LocalQuorum,
+            connect_retry: None,
+            token_signer: None,
+            retry_policy: None,
+            speculative_execution: None,
+            metrics: None,
+            slow_query_log: None,
+            redaction: Redaction::Off,
+            repair_migrations: false,
+            migration_lock: MigrationLock::new(),
+        }
+    }
+
+    /// Reads the configuration from a toml file, see [`InstanceConfig::from_env`] for the keys.
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let table = fs::read_to_string(path)
+            .map_err(|error| ConfigError::Read {
+                path: path.into(),
+                error,
+            })?
+            .parse::<toml::Table>()
+            .map_err(|error| ConfigError::Parse {
+                path: path.into(),
+                error,
+            })?;
+
+        Self::from_source(Source::Toml(&table))
+    }
+
+    /// Reads the configuration from environment variables named after the toml keys,
+    /// `app.instance` is read from `{prefix}_APP_INSTANCE`.
+    ///
+    /// Required keys are `app.instance`, `app.name`, `app.version` and `nodes`.
+    /// Optional keys are `credentials.username`, `credentials.password` or
+    /// `credentials.password_file`,
+    /// `replication.factor`, `replication.implementation`, `timeouts.connection`,
+    /// `timeouts.request`, `timeouts.schema_agreement`, `timeouts.keepalive_interval`,
+    /// `timeouts.keepalive_timeout`, `timeouts.tcp_keepalive_interval`, `compression`,
+    /// `pool_size_per_shard`,
+    /// `local_datacenter`, `load_balancing.rack`, `load_balancing.token_aware`,
+    /// `load_balancing.remote_dc_failover`, `consistency`, `tls.ca_bundle`, `tls.client_certificate`,
+    /// `tls.client_key`, `tls.verify_hostname`, `connect_retry.max_wait`,
+    /// `connect_retry.initial_delay`, `connect_retry.max_delay`, `connect_retry.jitter`,
+    /// `retry.policy`, `retry.attempts`, `retry.delay`, `retry.max_delay`,
+    /// `speculative_execution.policy`, `speculative_execution.max_retry_count`,
+    /// `speculative_execution.retry_interval`, `speculative_execution.percentile`,
+    /// `slow_query.threshold`, `slow_query.trace_sample_rate`, `redaction`,
+    /// `migrations.repair`, `migrations.lock_ttl` and `migrations.lock_wait`.
+    /// The other `tls`, `connect_retry`, `retry`, `speculative_execution` and `slow_query` keys
+    /// are only used when `tls.ca_bundle`, `connect_retry.max_wait`, `retry.policy`,
+    /// `speculative_execution.policy` and `slow_query.threshold` are set.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        Self::from_source(Source::Env(prefix))
+    }
+
+    /// Overrides the values that are set in the environment, see [`InstanceConfig::from_env`].
+    pub fn with_env(self, prefix: &str) -> Result<Self, ConfigError> {
+        self.apply(Source::Env(prefix))
+    }
+
+    fn from_source(source: Source) -> Result<Self, ConfigError> {
+        Self::new(
+            source.required("app.instance", source.parse("app.instance")?)?,
+            source.required("app.name", source.string("app.name")?)?,
+            source.required("app.version", source.string("app.version")?)?,
+            source.required("nodes", source.strings("nodes")?)?,
+        )
+        .apply(source)
+    }
+
+    fn apply(mut self, source: Source) -> Result<Self, ConfigError> {
+        if let Some(app_instance) = source.parse("app.instance")? {
+            self.app_instance = app_instance;
+        }
+        if let Some(app_name) = source.string("app.name")? {
+            self.app_name = app_name;
+        }
+        if let Some(app_version) = source.string("app.version")? {
+            self.app_version = app_version;
+        }
+        if let Some(nodes) = source.strings("nodes")? {
+            if nodes.is_empty() {
+                return Err(source.invalid("nodes", "at least one node is required"));
+            }
+            self.nodes = nodes;
+        }
+        self.apply_credentials(source)?;
+        self.apply_tls(source)?;
+        self.apply_retry(source)?;
+        if let Some(factor) = source.parse("replication.factor")? {
+            self.replication_factor = factor;
+        }
+        if let Some(implementation) = source.string("replication.implementation")? {
+            self.implementation = Some(implementation);
+        }
+        if let Some(timeout) = source.duration("timeouts.connection")? {
+            self.connection_timeout = timeout;
+        }
+        if let Some(timeout) = source.duration("timeouts.request")? {
+            self.request_timeout = Some(timeout);
+        }
+        if let Some(timeout) = source.duration("timeouts.schema_agreement")? {
+            self.schema_agreement_timeout = timeout;
+        }
+        if let Some(interval) = source.duration("timeouts.keepalive_interval")? {
+            self.keepalive_interval = Some(interval);
+        }
+        if let Some(timeout) = source.duration("timeouts.keepalive_timeout")? {
+            self.keepalive_timeout = Some(timeout);
+        }
+        if let Some(interval) = source.duration("timeouts.tcp_keepalive_interval")? {
+            self.tcp_keepalive_interval = Some(interval);
+        }
+        if let Some(compression) = source.parse("compression")? {
+            self.compression = compression;
+        }
+        if let Some(size) = source.parse("pool_size_per_shard")? {
+            self.pool_size_per_shard = Some(size);
+        }
+        self.apply_load_balancing(source)?;
+        if let Some(consistency) = source.parse_with("consistency", parse_consistency)? {
+            self.consistency = consistency;
+        }
+        if let Some(max_wait) = source.duration("connect_retry.max_wait")? {
+            self.connect_retry = Some(self.connect_retry.map_or_else(
+                || ConnectRetry::new(max_wait),
+                |retry| retry.max_wait(max_wait),
+            ));
+        }
+        if let Some(repair) = source.parse("migrations.repair")? {
+            self.repair_migrations = repair;
+        }
+        if let Some(ttl) = source.duration("migrations.lock_ttl")? {
+            self.migration_lock = self.migration_lock.ttl(ttl);
+        }
+        if let Some(wait) = source.duration("migrations.lock_wait")? {
+            self.migration_lock = self.migration_lock.wait(wait);
+        }
+        if let Some(redaction) = source.parse("redaction")? {
+            self.redaction = redaction;
+        }
+        if let Some(threshold) = source.duration("slow_query.threshold")? {
+            self.slow_query_log = Some(self.slow_query_log.map_or_else(
+                || SlowQueryLog::new(threshold),
+                |log| log.threshold(threshold),
+            ));
+        }
+        if let Some(mut log) = self.slow_query_log {
+            if let Some(rate) = source.parse("slow_query.trace_sample_rate")? {
+                log = log.trace_sample_rate(rate);
+            }
+            self.slow_query_log = Some(log);
+        }
+        if let Some(mut retry) = self.connect_retry {
+            if let Some(delay) = source.duration("connect_retry.initial_delay")? {
+                retry = retry.initial_delay(delay);
+            }
+            if let Some(delay) = source.duration("connect_retry.max_delay")? {
+                retry = retry.max_delay(delay);
+            }
+            if let Some(jitter) = source.parse("connect_retry.jitter")? {
+                retry = retry.jitter(jitter);
+            }
+            self.connect_retry = Some(retry);
+        }
+
+        Ok(self)
+    }
+
+    fn apply_credentials(&mut self, source: Source) -> Result<(), ConfigError> {
+        let password = match (
+            source.string("credentials.password")?,
+            source.string("credentials.password_file")?,
+        ) {
+            (Some(password), None) => Some(password.into()),
+            (None, Some(path)) => Some(Credentials::from_file("", path)?.password),
+            (Some(_), Some(_)) => {
+                return Err(source.invalid(
+                    "credentials.password_file",
+                    format!(
+                        "can not be combined with {}",
+                        source.name("credentials.password")
+                    ),
+                ));
+            }
+            (None, None) => None,
+        };
+
+        self.credentials = match (
+            source.string("credentials.username")?,
+            password,
+            self.credentials.take(),
+        ) {
+            (Some(username), Some(password), _) => Some(Credentials::new(username, password)),
+            (Some(username), None, Some(credentials)) => Some(Credentials {
+                username,
+                ..credentials
+            }),
+            (None, Some(password), Some(credentials)) => Some(Credentials {
+                password,
+                ..credentials
+            }),
+            (None, None, credentials) => credentials,
+            (Some(_), None, None) => {
+                return Err(ConfigError::MissingKey(source.name("credentials.password")));
+            }
+            (None, Some(_), None) => {
+                return Err(ConfigError::MissingKey(source.name("credentials.username")));
+            }
+        };
+
+        Ok(())
+    }
+
+    /// `load_balancing.remote_dc_failover` is the number of remote nodes that may be tried,
+    /// 0 disables failover.
+    fn apply_load_balancing(&mut self, source: Source) -> Result<(), ConfigError> {
+        if let Some(datacenter) = source.string("local_datacenter")? {
+            self.load_balancing.datacenter = Some(datacenter);
+        }
+        if let Some(rack) = source.string("load_balancing.rack")? {
+            self.load_balancing.rack = Some(rack);
+        }
+        if let Some(token_aware) = source.parse("load_balancing.token_aware")? {
+            self.load_balancing.token_aware = token_aware;
+        }
+        if let Some(limit) = source.parse("load_balancing.remote_dc_failover")? {
+            self.load_balancing.remote_dc_failover = Some(limit);
+        }
+        if self.load_balancing.rack.is_some() && self.load_balancing.datacenter.is_none() {
+            return Err(ConfigError::MissingKey(source.name("local_datacenter")));
+        }
+
+        Ok(())
+    }
+
+    fn apply_tls(&mut self, source: Source) -> Result<(), ConfigError> {
+        if let Some(ca_bundle) = source.string("tls.ca_bundle")? {
+            self.tls = Some(match self.tls.take() {
+                Some(tls) => TlsConfig {
+                    ca_bundle: ca_bundle.into(),
+                    ..tls
+                },
+                None => TlsConfig::new(ca_bundle),
+            });
+        }
+        if let Some(tls) = self.tls.take() {
+            let tls = match (
+                source.string("tls.client_certificate")?,
+                source.string("tls.client_key")?,
+            ) {
+                (Some(certificate), Some(key)) => tls.client_identity(certificate, key),
+                (Some(_), None) => {
+                    return Err(ConfigError::MissingKey(source.name("tls.client_key")));
+                }
+                (None, Some(_)) => {
+                    return Err(ConfigError::MissingKey(
+                        source.name("tls.client_certificate"),
+                    ));
+                }
+                (None, None) => tls,
+            };
+            self.tls = Some(match source.parse("tls.verify_hostname")? {
+                Some(verify) => tls.verify_hostname(verify),
+                None => tls,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// `retry.policy` is one of `off`, `fixed`, `exponential` or `downgrading_consistency`,
+    /// retries default to 3 attempts with a delay of 100ms, capped at 10s for `exponential`.
+    /// `speculative_execution.policy` is `simple` or `percentile`, defaulting to 2 additional
+    /// requests every 100ms or after the 99th percentile.
+    fn apply_retry(&mut self, source: Source) -> Result<(), ConfigError> {
+        if let Some(policy) = source.string("retry.policy")? {
+            // Switching the policy keeps the values of the one it replaces.
+            let (attempts, delay, max_delay) = match self.retry_policy {
+                Some(RetryPolicy::Fixed { attempts, delay }) => {
+                    (attempts, delay, Duration::from_secs(10))
+                }
+                Some(RetryPolicy::Exponential {
+                    attempts,
+                    initial_delay,
+                    max_delay,
+                }) => (attempts, initial_delay, max_delay),
+                _ => (3, Duration::from_millis(100), Duration::from_secs(10)),
+            };
+
+            self.retry_policy = Some(match policy.to_lowercase().replace('-', "_").as_str() {
+                "off" => RetryPolicy::Off,
+                "fixed" => RetryPolicy::Fixed { attempts, delay },
+                "exponential" => RetryPolicy::Exponential {
+                    attempts,
+                    initial_delay: delay,
+                    max_delay,
+                },
+                "downgrading_consistency" => RetryPolicy::DowngradingConsistency,
+                _ => {
+                    return Err(
+                        source.invalid("retry.policy", format!("unknown policy '{policy}'"))
+                    );
+                }
+            });
+        }
+        self.retry_policy = match self.retry_policy {
+            Some(RetryPolicy::Fixed { attempts, delay }) => Some(RetryPolicy::Fixed {
+                attempts: source.parse("retry.attempts")?.unwrap_or(attempts),
+                delay: source.duration("retry.delay")?.unwrap_or(delay),
+            }),
+            Some(RetryPolicy::Exponential {
+                attempts,
+                initial_delay,
+                max_delay,
+            }) => Some(RetryPolicy::Exponential {
+                attempts: source.parse("retry.attempts")?.unwrap_or(attempts),
+                initial_delay: source.duration("retry.delay")?.unwrap_or(initial_delay),
+                max_delay: source.duration("retry.max_delay")?.unwrap_or(max_delay),
+            }),
+            policy => policy,
+        };
+
+        if let Some(policy) = source.string("speculative_execution.policy")? {
+            let (max_retry_count, retry_interval, percentile) = match self.speculative_execution {
+                Some(SpeculativeExecution::Simple {
+                    max_retry_count,
+                    retry_interval,
+                }) => (max_retry_count, retry_interval, 99.0),
+                Some(SpeculativeExecution::Percentile {
+                    max_retry_count,
+                    percentile,
+                }) => (max_retry_count, Duration::from_millis(100), percentile),
+                None => (2, Duration::from_millis(100), 99.0),
+            };
+
+            self.speculative_execution = Some(match policy.to_lowercase().as_str() {
+                "simple" => SpeculativeExecution::Simple {
+                    max_retry_count,
+                    retry_interval,
+                },
+                "percentile" => SpeculativeExecution::Percentile {
+                    max_retry_count,
+                    percentile,
+                },
+                _ => {
+                    return Err(source.invalid(
+                        "speculative_execution.policy",
+                        format!("unknown policy '{policy}'"),
+                    ));
+                }
+            });
+        }
+        self.speculative_execution = match self.speculative_execution {
+            Some(SpeculativeExecution::Simple {
+                max_retry_count,
+                retry_interval,
+            }) => Some(SpeculativeExecution::Simple {
+                max_retry_count: source
+                    .parse("speculative_execution.max_retry_count")?
+                    .unwrap_or(max_retry_count),
+                retry_interval: source
+                    .duration("speculative_execution.retry_interval")?
+                    .unwrap_or(retry_interval),
+            }),
+            Some(SpeculativeExecution::Percentile {
+                max_retry_count,
+                percentile,
+            }) => Some(SpeculativeExecution::Percentile {
+                max_retry_count: source
+                    .parse("speculative_execution.max_retry_count")?
+                    .unwrap_or(max_retry_count),
+                percentile: source
+                    .parse("speculative_execution.percentile")?
+                    .unwrap_or(percentile),
+            }),
+            None => None,
+        };
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    #[must_use]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    #[must_use]
+    pub const fn replication_factor(mut self, replication_factor: usize) -> Self {
+        self.replication_factor = replication_factor;
+        self
+    }
+
+    #[must_use]
+    pub fn implementation(mut self, implementation: impl Into<String>) -> Self {
+        self.implementation = Some(implementation.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = timeout;
+        self
+    }
+
+    /// `None` disables the client side timeout and leaves it to the server.
+    #[must_use]
+    pub const fn request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// How long a statement that changes the schema waits for all nodes to agree on it.
+    /// Migrations and [`Instance::setup`](crate::scylla::Instance::setup) fail with a dedicated
+    /// error when they don't agree in time.
+    #[must_use]
+    pub const fn schema_agreement_timeout(mut self, timeout: Duration) -> Self {
+        self.schema_agreement_timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub const fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Interval of CQL `OPTIONS` keepalive requests sent on idle connections.
+    #[must_use]
+    pub const fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// How long to wait for a keepalive response before the connection is closed.
+    #[must_use]
+    pub const fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub const fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive_interval = Some(interval);
+        self
+    }
+
+    #[must_use]
+    pub const fn pool_size_per_shard(mut self, size: NonZeroUsize) -> Self {
+        self.pool_size_per_shard = Some(size);
+        self
+    }
+
+    #[must_use]
+    pub fn local_datacenter(mut self, datacenter: impl Into<String>) -> Self {
+        self.load_balancing.datacenter = Some(datacenter.into());
+        self
+    }
+
+    #[must_use]
+    pub fn load_balancing(mut self, load_balancing: LoadBalancing) -> Self {
+        self.load_balancing = load_balancing;
+        self
+    }
+
+    #[must_use]
+    pub const fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Retries connecting while the cluster is unreachable instead of failing immediately.
+    #[must_use]
+    pub const fn connect_retry(mut self, retry: ConnectRetry) -> Self {
+        self.connect_retry = Some(retry);
+        self
+    }
+
+    /// Default retry policy, can be overridden per query with [`QueryOptions::retry_policy`].
+    #[must_use]
+    pub const fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Default speculative execution policy, can be overridden per query with
+    /// [`QueryOptions::speculative_execution`].
+    #[must_use]
+    pub const fn speculative_execution(mut self, policy: SpeculativeExecution) -> Self {
+        self.speculative_execution = Some(policy);
+        self
+    }
+
+    /// Receives latency, error, row, page and prepared cache measurements of the executed
+    /// statements.
+    #[must_use]
+    pub fn metrics(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
+    /// Logs a warning for statements that take longer than the threshold of `log`.
+    #[must_use]
+    pub const fn slow_query_log(mut self, log: SlowQueryLog) -> Self {
+        self.slow_query_log = Some(log);
+        self
+    }
+
+    /// How statement text appears in logs, metrics and errors.
+    #[must_use]
+    pub const fn redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Accepts applied migration statements that were changed since, recording their new
+    /// checksum instead of failing with
+    /// [`MigrationError::ChecksumMismatch`](crate::scylla::MigrationError::ChecksumMismatch).
+    #[must_use]
+    pub const fn repair_migrations(mut self, repair: bool) -> Self {
+        self.repair_migrations = repair;
+        self
+    }
+
+    #[must_use]
+    pub const fn migration_lock(mut self, lock: MigrationLock) -> Self {
+        self.migration_lock = lock;
+        self
+    }
+
+    /// Key used to sign the tokens of
+    /// [`Instance::query_page_signed`](crate::scylla::Instance::query_page_signed), tokens are
+    /// rejected once `ttl` has passed.
+    #[must_use]
+    pub fn continuation_key(mut self, key: SecretVec<u8>, ttl: Duration) -> Self {
+        self.token_signer = Some(TokenSigner::new(key, ttl).into());
+        self
+    }
+
+    pub(crate) const fn query_options(&self) -> QueryOptions {
+        let mut options = QueryOptions::new();
+
+        if let Some(policy) = self.retry_policy {
+            options = options.retry_policy(policy);
+        }
+        if let Some(policy) = self.speculative_execution {
+            options = options.speculative_execution(policy);
+        }
+
+        options
+    }
+
+    pub(crate) fn session_builder(
+        &self,
+        credentials: Arc<CredentialsProvider>,
+    ) -> Result<SessionBuilder, TlsError> {
+        let mut builder = SessionBuilder::new()
+            .known_nodes(&self.nodes)
+            .connection_timeout(self.connection_timeout)
+            .schema_agreement_timeout(self.schema_agreement_timeout)
+            .compression(self.compression.into())
+            .authenticator_provider(credentials)
+            .default_execution_profile_handle(self.execution_profile().into_handle());
+
+        if let Some(interval) = self.keepalive_interval {
+            builder = builder.keepalive_interval(interval);
+        }
+        if let Some(timeout) = self.keepalive_timeout {
+            builder = builder.keepalive_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive_interval {
+            builder = builder.tcp_keepalive_interval(interval);
+        }
+        if let Some(size) = self.pool_size_per_shard {
+            builder = builder.pool_size(PoolSize::PerShard(size));
+        }
+        if let Some(tls) = &self.tls {
+            builder = builder.tls_context(Some(tls.context()?));
+        }
+
+        Ok(builder)
+    }
+
+    fn execution_profile(&self) -> ExecutionProfile {
+        ExecutionProfile::builder()
+            .consistency(self.consistency)
+            .request_timeout(self.request_timeout)
+            .load_balancing_policy(self.load_balancing.policy())
+            .build()
+    }
+}
+
+fn parse_consistency(value: &str) -> Result<Consistency, String> {
+    Ok(match value.to_uppercase().replace('-', "_").as_str() {
+        "ANY" => Consistency::Any,
+        "ONE" => Consistency::One,
+        "TWO" => Consistency::Two,
+        "THREE" => Consistency::Three,
+        "QUORUM" => Consistency::Quorum,
+        "ALL" => Consistency::All,
+        "LOCAL_QUORUM" => Consistency::LocalQuorum,
+        "EACH_QUORUM" => Consistency::EachQuorum,
+        "LOCAL_ONE" => Consistency::LocalOne,
+        "SERIAL" => Consistency::Serial,
+        "LOCAL_SERIAL" => Consistency::LocalSerial,
+        _ => return Err(format!("unknown consistency '{value}'")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const FILE: &str = r#"
+        nodes = ["localhost:9042"]
+
+        [app]
+        instance = "00000000-0000-0000-0000-000000000001"
+        name = "app"
+        version = "1.0.0"
+
+        [retry]
+        policy = "fixed"
+        attempts = 7
+        delay = "1s"
+
+        [speculative_execution]
+        policy = "percentile"
+        max_retry_count = 4
+
+        [connect_retry]
+        max_wait = "10s"
+        initial_delay = "3s"
+
+        [slow_query]
+        threshold = "1s"
+        trace_sample_rate = 0.5
+    "#;
+
+    fn file() -> InstanceConfig {
+        InstanceConfig::from_source(Source::Toml(&FILE.parse().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn overrides_keep_the_other_keys_of_a_policy() {
+        let config = file()
+            .apply(Source::Toml(
+                &r#"retry.policy = "exponential""#.parse().unwrap(),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            config.retry_policy,
+            Some(RetryPolicy::Exponential {
+                attempts: 7,
+                initial_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(10),
+            })
+        );
+    }
+
+    /// Every test reading the environment is in here, as tests run in parallel.
+    #[test]
+    fn env_overrides_file_keys() {
+        const PREFIX: &str = "LIB_PERSIST_CONFIG_TEST";
+        let set = |key: &str, value: &str| {
+            // SAFETY: no other test reads or writes the environment.
+            unsafe { env::set_var(format!("{PREFIX}_{key}"), value) };
+        };
+
+        set("APP_NAME", "other");
+        set("RETRY_ATTEMPTS", "1");
+        set("SPECULATIVE_EXECUTION_PERCENTILE", "95");
+        set("CONNECT_RETRY_MAX_WAIT", "20s");
+        set("SLOW_QUERY_THRESHOLD", "2s");
+
+        let config = file().with_env(PREFIX).unwrap();
+
+        assert_eq!(config.app_name, "other");
+        assert_eq!(config.app_version, "1.0.0");
+        assert_eq!(
+            config.retry_policy,
+            Some(RetryPolicy::Fixed {
+                attempts: 1,
+                delay: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(
+            config.speculative_execution,
+            Some(SpeculativeExecution::Percentile {
+                max_retry_count: 4,
+                percentile: 95.0,
+            })
+        );
+        let connect_retry = config.connect_retry.unwrap();
+        assert_eq!(connect_retry.max_wait, Duration::from_secs(20));
+        assert_eq!(connect_retry.initial_delay, Duration::from_secs(3));
+        assert_eq!(
+            config.slow_query_log,
+            Some(SlowQueryLog::new(Duration::from_secs(2)).trace_sample_rate(0.5))
+        );
+
+        set("RETRY_ATTEMPTS", "many");
+        assert!(matches!(
+            file().with_env(PREFIX),
+            Err(ConfigError::InvalidValue { key, .. }) if key == "LIB_PERSIST_CONFIG_TEST_RETRY_ATTEMPTS"
+        ));
+
+        set("RETRY_ATTEMPTS", "1");
+        set("CREDENTIALS_USERNAME", "user");
+        assert!(matches!(
+            file().with_env(PREFIX),
+            Err(ConfigError::MissingKey(key)) if key == "LIB_PERSIST_CONFIG_TEST_CREDENTIALS_PASSWORD"
+        ));
+
+        assert!(matches!(
+            InstanceConfig::from_env("LIB_PERSIST_CONFIG_UNSET"),
+            Err(ConfigError::MissingKey(key)) if key == "LIB_PERSIST_CONFIG_UNSET_APP_INSTANCE"
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use rand::random_range;
+use std::time::Duration;
+
+/// Exponential backoff used by [`Instance::connect`](crate::scylla::Instance::connect) while the
+/// cluster is unreachable.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectRetry {
+    pub(crate) initial_delay: Duration,
+    pub(crate) max_delay: Duration,
+    pub(crate) max_wait: Duration,
+    pub(crate) jitter: f64,
+}
+
+impl ConnectRetry {
+    /// Keeps retrying until `max_wait` has passed since the first attempt.
+    #[must_use]
+    pub const fn new(max_wait: Duration) -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_wait,
+            jitter: 0.2,
+        }
+    }
+
+    #[must_use]
+    pub const fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    #[must_use]
+    pub const fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    #[must_use]
+    pub const fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Fraction of the delay that is randomly added or removed, clamped to `0.0..=1.0`.
+    #[must_use]
+    pub const fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        if self.jitter > 0.0 {
+            delay.mul_f64(random_range(1.0 - self.jitter..=1.0 + self.jitter))
+        } else {
+            delay
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::scylla::ConfigError;
+use async_trait::async_trait;
+use scylla::authentication::{AuthError, AuthenticatorProvider, AuthenticatorSession};
+use secrecy::{ExposeSecret, SecretString};
+use std::{
+    env, fs,
+    path::Path,
+    sync::{PoisonError, RwLock},
+};
+
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub(super) username: String,
+    pub(super) password: SecretString,
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: SecretString) -> Self {
+        Self {
+            username: username.into(),
+            password,
+        }
+    }
+
+    /// Reads the password from `path`, surrounding whitespace is ignored.
+    pub fn from_file(
+        username: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let password = fs::read_to_string(path).map_err(|error| ConfigError::Read {
+            path: path.into(),
+            error,
+        })?;
+
+        Ok(Self::new(username, password.trim().to_string().into()))
+    }
+
+    /// Reads the password from the environment variable `name`.
+    pub fn from_env(username: impl Into<String>, name: &str) -> Result<Self, ConfigError> {
+        match env::var(name) {
+            Ok(password) => Ok(Self::new(username, password.into())),
+            Err(env::VarError::NotPresent) => Err(ConfigError::MissingKey(name.to_string())),
+            Err(err) => Err(ConfigError::InvalidValue {
+                key: name.to_string(),
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    #[must_use]
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// Plain text authentication with credentials that can be replaced while the session is running.
+/// Only connections opened after a rotation use the new credentials.
+pub struct CredentialsProvider(RwLock<Option<Credentials>>);
+
+impl CredentialsProvider {
+    pub const fn new(credentials: Option<Credentials>) -> Self {
+        Self(RwLock::new(credentials))
+    }
+
+    pub fn rotate(&self, credentials: Credentials) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(credentials);
+    }
+}
+
+#[async_trait]
+impl AuthenticatorProvider for CredentialsProvider {
+    async fn start_authentication_session(
+        &self,
+        _authenticator_name: &str,
+    ) -> Result<(Option<Vec<u8>>, Box<dyn AuthenticatorSession>), AuthError> {
+        let response = self
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map(|credentials| {
+                let username = credentials.username.as_bytes();
+                let password = credentials.password.expose_secret().as_bytes();
+
+                let mut response = Vec::with_capacity(username.len() + password.len() + 2);
+                response.push(0);
+                response.extend_from_slice(username);
+                response.push(0);
+                response.extend_from_slice(password);
+                response
+            })
+            .ok_or_else(|| {
+                "Authentication requested but no credentials are configured".to_string()
+            })?;
+
+        Ok((Some(response), Box::new(PlainTextSession)))
+    }
+}
+
+struct PlainTextSession;
+
+#[async_trait]
+impl AuthenticatorSession for PlainTextSession {
+    async fn evaluate_challenge(
+        &mut self,
+        _token: Option<&[u8]>,
+    ) -> Result<Option<Vec<u8>>, AuthError> {
+        Err("Challenges are not expected during plain text authentication".to_string())
+    }
+
+    async fn success(&mut self, _token: Option<&[u8]>) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
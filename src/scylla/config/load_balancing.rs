@@ -0,0 +1,142 @@
+use scylla::{
+    cluster::{ClusterState, NodeRef},
+    errors::RequestAttemptError,
+    policies::load_balancing::{DefaultPolicy, FallbackPlan, LoadBalancingPolicy, RoutingInfo},
+    routing::Shard,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Which nodes requests are sent to. Without a datacenter all nodes are treated equally.
+#[derive(Clone, Debug)]
+pub struct LoadBalancing {
+    pub(super) datacenter: Option<String>,
+    pub(super) rack: Option<String>,
+    pub(super) token_aware: bool,
+    pub(super) remote_dc_failover: Option<usize>,
+}
+
+impl Default for LoadBalancing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadBalancing {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            datacenter: None,
+            rack: None,
+            token_aware: true,
+            remote_dc_failover: None,
+        }
+    }
+
+    /// Local datacenter, requests are only sent to its nodes unless remote failover is permitted.
+    #[must_use]
+    pub fn datacenter(mut self, datacenter: impl Into<String>) -> Self {
+        self.datacenter = Some(datacenter.into());
+        self
+    }
+
+    /// Nodes in this rack of the local datacenter are tried first, ignored without a datacenter.
+    #[must_use]
+    pub fn rack(mut self, rack: impl Into<String>) -> Self {
+        self.rack = Some(rack.into());
+        self
+    }
+
+    /// Sends requests to replicas of the bound partition first, enabled by default.
+    #[must_use]
+    pub const fn token_aware(mut self, token_aware: bool) -> Self {
+        self.token_aware = token_aware;
+        self
+    }
+
+    /// Tries at most `limit` nodes of other datacenters once the local nodes have failed.
+    #[must_use]
+    pub const fn remote_dc_failover(mut self, limit: usize) -> Self {
+        self.remote_dc_failover = Some(limit);
+        self
+    }
+
+    pub(super) fn policy(&self) -> Arc<dyn LoadBalancingPolicy> {
+        let mut builder = DefaultPolicy::builder()
+            .token_aware(self.token_aware)
+            .permit_dc_failover(self.remote_dc_failover.is_some_and(|limit| limit > 0));
+
+        let Some(datacenter) = &self.datacenter else {
+            return builder.build();
+        };
+
+        builder = match &self.rack {
+            Some(rack) => builder.prefer_datacenter_and_rack(datacenter.clone(), rack.clone()),
+            None => builder.prefer_datacenter(datacenter.clone()),
+        };
+
+        match self.remote_dc_failover {
+            Some(limit) if limit > 0 => Arc::new(RemoteLimit {
+                inner: builder.build(),
+                datacenter: datacenter.clone(),
+                limit,
+            }),
+            _ => builder.build(),
+        }
+    }
+}
+
+/// Caps the number of nodes outside the local datacenter in the fallback plan.
+#[derive(Debug)]
+struct RemoteLimit {
+    inner: Arc<dyn LoadBalancingPolicy>,
+    datacenter: String,
+    limit: usize,
+}
+
+impl LoadBalancingPolicy for RemoteLimit {
+    fn pick<'a>(
+        &'a self,
+        request: &'a RoutingInfo,
+        cluster: &'a ClusterState,
+    ) -> Option<(NodeRef<'a>, Option<Shard>)> {
+        self.inner.pick(request, cluster)
+    }
+
+    fn fallback<'a>(
+        &'a self,
+        request: &'a RoutingInfo,
+        cluster: &'a ClusterState,
+    ) -> FallbackPlan<'a> {
+        let mut remote = 0;
+
+        Box::new(
+            self.inner
+                .fallback(request, cluster)
+                .filter(move |(node, _)| {
+                    if node.datacenter.as_deref() == Some(self.datacenter.as_str()) {
+                        return true;
+                    }
+                    remote += 1;
+                    remote <= self.limit
+                }),
+        )
+    }
+
+    fn on_request_success(&self, request: &RoutingInfo, latency: Duration, node: NodeRef<'_>) {
+        self.inner.on_request_success(request, latency, node);
+    }
+
+    fn on_request_failure(
+        &self,
+        request: &RoutingInfo,
+        latency: Duration,
+        node: NodeRef<'_>,
+        error: &RequestAttemptError,
+    ) {
+        self.inner.on_request_failure(request, latency, node, error);
+    }
+
+    fn name(&self) -> String {
+        format!("RemoteLimit({})", self.inner.name())
+    }
+}
@@ -0,0 +1,177 @@
+use crate::scylla::ConfigError;
+use std::{env, fmt::Display, str::FromStr, time::Duration};
+use toml::{Table, Value};
+
+#[derive(Clone, Copy)]
+pub enum Source<'a> {
+    Toml(&'a Table),
+    Env(&'a str),
+}
+
+impl Source<'_> {
+    pub fn string(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        match self {
+            Self::Toml(table) => match lookup(table, key) {
+                None => Ok(None),
+                Some(Value::String(value)) => Ok(Some(value.clone())),
+                Some(Value::Integer(value)) => Ok(Some(value.to_string())),
+                Some(Value::Float(value)) => Ok(Some(value.to_string())),
+                Some(Value::Boolean(value)) => Ok(Some(value.to_string())),
+                Some(value) => Err(self.invalid(key, format!("unexpected {}", value.type_str()))),
+            },
+            Self::Env(_) => match env::var(self.name(key)) {
+                Ok(value) => Ok(Some(value)),
+                Err(env::VarError::NotPresent) => Ok(None),
+                Err(err) => Err(self.invalid(key, err)),
+            },
+        }
+    }
+
+    /// Toml arrays of strings, or comma separated values in environment variables.
+    pub fn strings(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        match self {
+            Self::Toml(table) => match lookup(table, key) {
+                None => Ok(None),
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .map(|value| {
+                        value.as_str().map(str::to_string).ok_or_else(|| {
+                            self.invalid(key, format!("unexpected {} in array", value.type_str()))
+                        })
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Some),
+                Some(value) => Err(self.invalid(key, format!("unexpected {}", value.type_str()))),
+            },
+            Self::Env(_) => Ok(self.string(key)?.map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })),
+        }
+    }
+
+    pub fn parse<T>(&self, key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.parse_with(key, |value| {
+            value.parse::<T>().map_err(|err| err.to_string())
+        })
+    }
+
+    pub fn parse_with<T>(
+        &self,
+        key: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, ConfigError> {
+        self.string(key)?
+            .map(|value| parse(value.trim()).map_err(|reason| self.invalid(key, reason)))
+            .transpose()
+    }
+
+    /// Durations are written as an integer followed by `ms`, `s`, `m` or `h`.
+    pub fn duration(&self, key: &str) -> Result<Option<Duration>, ConfigError> {
+        self.parse_with(key, parse_duration)
+    }
+
+    pub fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, ConfigError> {
+        value.ok_or_else(|| ConfigError::MissingKey(self.name(key)))
+    }
+
+    pub fn name(&self, key: &str) -> String {
+        match self {
+            Self::Toml(_) => key.to_string(),
+            Self::Env("") => key.replace('.', "_").to_uppercase(),
+            Self::Env(prefix) => format!("{prefix}_{}", key.replace('.', "_").to_uppercase()),
+        }
+    }
+
+    pub fn invalid(&self, key: &str, reason: impl Display) -> ConfigError {
+        ConfigError::InvalidValue {
+            key: self.name(key),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let first = table.get(parts.next()?)?;
+    parts.try_fold(first, |value, part| value.as_table()?.get(part))
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("'{value}' is missing a unit (ms, s, m or h)"))?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount
+        .parse::<u64>()
+        .map_err(|err| format!("'{value}': {err}"))?;
+
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => amount.checked_mul(60).map(Duration::from_secs),
+        "h" => amount.checked_mul(60 * 60).map(Duration::from_secs),
+        unit => return Err(format!("'{value}' has unknown unit '{unit}'")),
+    }
+    .ok_or_else(|| format!("'{value}' is out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("3s"), Ok(Duration::from_secs(3)));
+        assert_eq!(parse_duration("2 m"), Ok(Duration::from_mins(2)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_hours(1)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for value in ["5", "5d", "s", "-5s", "1.5s"] {
+            assert!(parse_duration(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn duration_overflow() {
+        assert_eq!(
+            parse_duration(&format!("{}h", u64::MAX)),
+            Err(format!("'{}h' is out of range", u64::MAX))
+        );
+        assert!(parse_duration("99999999999999999999s").is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Source::Env("").name("retry.attempts"), "RETRY_ATTEMPTS");
+        assert_eq!(
+            Source::Env("APP").name("retry.max_delay"),
+            "APP_RETRY_MAX_DELAY"
+        );
+        assert_eq!(
+            Source::Toml(&Table::new()).name("retry.attempts"),
+            "retry.attempts"
+        );
+    }
+
+    #[test]
+    fn invalid_toml_values() {
+        let table = "retry = { attempts = [1] }".parse::<Table>().unwrap();
+
+        assert!(matches!(
+            Source::Toml(&table).string("retry.attempts"),
+            Err(ConfigError::InvalidValue { key, .. }) if key == "retry.attempts"
+        ));
+    }
+}
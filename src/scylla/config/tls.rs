@@ -0,0 +1,147 @@
+use crate::scylla::TlsError;
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub(super) ca_bundle: PathBuf,
+    pub(super) client_identity: Option<(PathBuf, PathBuf)>,
+    pub(super) verify_hostname: bool,
+}
+
+impl TlsConfig {
+    /// `ca_bundle` is a PEM file with the certificates used to verify the nodes.
+    pub fn new(ca_bundle: impl Into<PathBuf>) -> Self {
+        Self {
+            ca_bundle: ca_bundle.into(),
+            client_identity: None,
+            verify_hostname: true,
+        }
+    }
+
+    /// PEM files with the client certificate chain and private key used for mutual TLS.
+    #[must_use]
+    pub fn client_identity(
+        mut self,
+        certificate: impl Into<PathBuf>,
+        private_key: impl Into<PathBuf>,
+    ) -> Self {
+        self.client_identity = Some((certificate.into(), private_key.into()));
+        self
+    }
+
+    /// Nodes are connected to by address, so the certificates have to contain the node address
+    /// as a subject alternative name unless this is disabled. The certificate chain is always
+    /// verified against the CA bundle.
+    #[must_use]
+    pub const fn verify_hostname(mut self, verify: bool) -> Self {
+        self.verify_hostname = verify;
+        self
+    }
+
+    pub(crate) fn context(&self) -> Result<Arc<ClientConfig>, TlsError> {
+        let provider = Arc::new(ring::default_provider());
+
+        let mut roots = RootCertStore::empty();
+        for certificate in read_certificates(&self.ca_bundle)? {
+            roots.add(certificate)?;
+        }
+
+        let verifier =
+            WebPkiServerVerifier::builder_with_provider(roots.into(), provider.clone()).build()?;
+
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(if self.verify_hostname {
+                verifier
+            } else {
+                Arc::new(SkipHostnameVerification(verifier))
+            });
+
+        Ok(match &self.client_identity {
+            Some((certificate, private_key)) => builder.with_client_auth_cert(
+                read_certificates(certificate)?,
+                PrivateKeyDer::from_pem_file(private_key).map_err(|error| TlsError::Pem {
+                    path: private_key.clone(),
+                    error,
+                })?,
+            )?,
+            None => builder.with_no_client_auth(),
+        }
+        .into())
+    }
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_err(|error| TlsError::Pem {
+            path: path.into(),
+            error,
+        })?;
+
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(path.into()));
+    }
+
+    Ok(certificates)
+}
+
+#[derive(Debug)]
+struct SkipHostnameVerification(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for SkipHostnameVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self
+            .0
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::NotValidForName
+                | rustls::CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
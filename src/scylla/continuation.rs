@@ -0,0 +1,245 @@
+use crate::scylla::{
+    Cursor, CursorError, Error,
+    cursor::{decode, encode},
+};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretVec};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const VERSION: u8 = 1;
+/// Version byte followed by the expiry in seconds since the unix epoch.
+const HEADER_LEN: usize = 1 + 8;
+const MAC_LEN: usize = 32;
+
+/// Position after a page returned by
+/// [`Instance::query_page_signed`](crate::scylla::Instance::query_page_signed).
+///
+/// Carries the paging state together with an expiry and a HMAC over both, the statement text
+/// and the bound values, so it can be handed to untrusted clients. Formats as an URL safe
+/// string that is parsed back with [`str::parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContinuationToken(Vec<u8>);
+
+impl fmt::Display for ContinuationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(&self.0, f)
+    }
+}
+
+impl FromStr for ContinuationToken {
+    type Err = CursorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        decode(value).map(Self)
+    }
+}
+
+pub enum TokenError {
+    Mismatch,
+    Expired,
+}
+
+impl From<TokenError> for Error {
+    fn from(err: TokenError) -> Self {
+        match err {
+            TokenError::Mismatch => Self::TokenMismatch,
+            TokenError::Expired => Self::TokenExpired,
+        }
+    }
+}
+
+pub struct TokenSigner {
+    key: SecretVec<u8>,
+    ttl: Duration,
+}
+
+impl fmt::Debug for TokenSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenSigner")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenSigner {
+    pub const fn new(key: SecretVec<u8>, ttl: Duration) -> Self {
+        Self { key, ttl }
+    }
+
+    pub fn sign(&self, cursor: &Cursor, statement: &str, values: &[u8]) -> ContinuationToken {
+        self.sign_at(cursor, statement, values, SystemTime::now())
+    }
+
+    pub fn verify(
+        &self,
+        token: &ContinuationToken,
+        statement: &str,
+        values: &[u8],
+    ) -> Result<Cursor, TokenError> {
+        self.verify_at(token, statement, values, SystemTime::now())
+    }
+
+    fn sign_at(
+        &self,
+        cursor: &Cursor,
+        statement: &str,
+        values: &[u8],
+        now: SystemTime,
+    ) -> ContinuationToken {
+        let expires = (now + self.ttl)
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |expires| expires.as_secs());
+
+        let mut token = Vec::with_capacity(HEADER_LEN + cursor.as_bytes().len() + MAC_LEN);
+        token.push(VERSION);
+        token.extend_from_slice(&expires.to_be_bytes());
+        token.extend_from_slice(cursor.as_bytes());
+
+        let mac = self.mac(&token, statement, values).finalize();
+        token.extend_from_slice(&mac.into_bytes());
+
+        ContinuationToken(token)
+    }
+
+    fn verify_at(
+        &self,
+        token: &ContinuationToken,
+        statement: &str,
+        values: &[u8],
+        now: SystemTime,
+    ) -> Result<Cursor, TokenError> {
+        if token.0.len() <= HEADER_LEN + MAC_LEN || token.0[0] != VERSION {
+            return Err(TokenError::Mismatch);
+        }
+
+        let (payload, mac) = token.0.split_at(token.0.len() - MAC_LEN);
+
+        self.mac(payload, statement, values)
+            .verify_slice(mac)
+            .map_err(|_| TokenError::Mismatch)?;
+
+        let mut expires = [0; 8];
+        expires.copy_from_slice(&payload[1..HEADER_LEN]);
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        if now > u64::from_be_bytes(expires) {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(Cursor::from_bytes(&payload[HEADER_LEN..]))
+    }
+
+    fn mac(&self, payload: &[u8], statement: &str, values: &[u8]) -> Hmac<Sha256> {
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(self.key.expose_secret()) else {
+            unreachable!("HMAC accepts keys of any length");
+        };
+
+        mac.update(
+            &u64::try_from(payload.len())
+                .unwrap_or(u64::MAX)
+                .to_be_bytes(),
+        );
+        mac.update(payload);
+        mac.update(&Sha256::digest(statement.as_bytes()));
+        mac.update(values);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATEMENT: &str = "select * from items where tenant = ?";
+    const VALUES: &[u8] = b"tenant-a";
+
+    fn signer() -> TokenSigner {
+        TokenSigner::new(SecretVec::new(b"secret".to_vec()), Duration::from_mins(5))
+    }
+
+    fn cursor() -> Cursor {
+        Cursor::from_bytes(b"paging state")
+    }
+
+    #[test]
+    fn round_trip() {
+        let signer = signer();
+        let token = signer.sign(&cursor(), STATEMENT, VALUES);
+        let parsed = token.to_string().parse::<ContinuationToken>().ok();
+
+        assert_eq!(parsed.as_ref(), Some(&token));
+        assert_eq!(
+            signer.verify(&token, STATEMENT, VALUES).ok(),
+            Some(cursor())
+        );
+    }
+
+    #[test]
+    fn tampered_payload() {
+        let signer = signer();
+        let mut token = signer.sign(&cursor(), STATEMENT, VALUES);
+        token.0[HEADER_LEN] ^= 1;
+
+        assert!(matches!(
+            signer.verify(&token, STATEMENT, VALUES),
+            Err(TokenError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn other_statement() {
+        let signer = signer();
+        let token = signer.sign(&cursor(), STATEMENT, VALUES);
+
+        assert!(matches!(
+            signer.verify(&token, "select * from items", VALUES),
+            Err(TokenError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn other_values() {
+        let signer = signer();
+        let token = signer.sign(&cursor(), STATEMENT, VALUES);
+
+        assert!(matches!(
+            signer.verify(&token, STATEMENT, b"tenant-b"),
+            Err(TokenError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn other_key() {
+        let token = signer().sign(&cursor(), STATEMENT, VALUES);
+        let other = TokenSigner::new(SecretVec::new(b"other".to_vec()), Duration::from_mins(5));
+
+        assert!(matches!(
+            other.verify(&token, STATEMENT, VALUES),
+            Err(TokenError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn expired() {
+        let signer = signer();
+        let now = SystemTime::now();
+        let token = signer.sign_at(&cursor(), STATEMENT, VALUES, now);
+
+        assert!(
+            signer
+                .verify_at(&token, STATEMENT, VALUES, now + Duration::from_mins(4))
+                .is_ok()
+        );
+        assert!(matches!(
+            signer.verify_at(&token, STATEMENT, VALUES, now + Duration::from_mins(6)),
+            Err(TokenError::Expired)
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::scylla::CursorError;
+use scylla::response::{PagingState, PagingStateResponse};
+use std::{fmt, str::FromStr, sync::Arc};
+
+/// Bytes encoded per chunk, together with a marker byte they fit in an `u128`.
+const CHUNK_BYTES: usize = 15;
+/// Base62 characters needed for any `u128`, chunks are zero padded to this length.
+const CHUNK_LEN: usize = 22;
+
+/// Position after a page returned by [`Instance::query_page`](crate::scylla::Instance::query_page).
+///
+/// Formats as an URL safe string that is parsed back with [`str::parse`]. The cursor is only
+/// meaningful for the statement and values that produced it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor(Arc<[u8]>);
+
+#[derive(Debug)]
+pub struct Page<T, C = Cursor> {
+    pub rows: Vec<T>,
+    pub next: Option<C>,
+}
+
+impl Cursor {
+    pub(crate) fn from_response(response: PagingStateResponse) -> Option<Self> {
+        match response {
+            PagingStateResponse::HasMorePages { state } => {
+                state.as_bytes_slice().cloned().map(Self)
+            }
+            PagingStateResponse::NoMorePages => None,
+        }
+    }
+
+    pub(crate) fn paging_state(&self) -> PagingState {
+        PagingState::new_from_raw_bytes(self.0.clone())
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(&self.0, f)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = CursorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        decode(value).map(|bytes| Self(bytes.into()))
+    }
+}
+
+pub fn encode(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    bytes.chunks(CHUNK_BYTES).try_for_each(|chunk| {
+        let value = chunk
+            .iter()
+            .fold(1_u128, |value, byte| value << 8 | u128::from(*byte));
+        write!(f, "{:0>CHUNK_LEN$}", base62::encode(value))
+    })
+}
+
+pub fn decode(value: &str) -> Result<Vec<u8>, CursorError> {
+    if value.is_empty() || !value.len().is_multiple_of(CHUNK_LEN) {
+        return Err(CursorError);
+    }
+
+    value
+        .as_bytes()
+        .chunks(CHUNK_LEN)
+        .map(|chunk| {
+            let value = base62::decode(chunk).map_err(|_| CursorError)?;
+            let marker = value.checked_ilog2().ok_or(CursorError)?;
+            if marker == 0 || !marker.is_multiple_of(8) {
+                return Err(CursorError);
+            }
+            let len = usize::try_from(marker / 8).map_err(|_| CursorError)?;
+            Ok(value.to_be_bytes()[16 - len..].to_vec())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|chunks| chunks.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for len in [1_usize, 15, 16, 30] {
+            let bytes = (0..=u8::MAX).rev().take(len).collect::<Vec<_>>();
+            let cursor = Cursor::from_bytes(&bytes);
+            let value = cursor.to_string();
+
+            assert_eq!(value.len(), len.div_ceil(CHUNK_BYTES) * CHUNK_LEN);
+            assert_eq!(
+                value.parse::<Cursor>().ok().as_ref().map(Cursor::as_bytes),
+                Some(&bytes[..])
+            );
+        }
+    }
+
+    #[test]
+    fn leading_zero_bytes_round_trip() {
+        let cursor = Cursor::from_bytes(&[0; 16]);
+
+        assert_eq!(cursor.to_string().parse().ok(), Some(cursor));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let valid = Cursor::from_bytes(&[1; 16]).to_string();
+        let cases = [
+            String::new(),
+            valid[1..].to_owned(),
+            format!("{valid}0"),
+            // Markers of zero, of no bytes and of a partial byte.
+            "0".repeat(CHUNK_LEN),
+            format!("{:0>CHUNK_LEN$}", 1),
+            format!("{:0>CHUNK_LEN$}", 2),
+            "!".repeat(CHUNK_LEN),
+        ];
+
+        for value in cases {
+            assert!(value.parse::<Cursor>().is_err(), "{value}");
+        }
+    }
+}
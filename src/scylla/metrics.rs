@@ -0,0 +1,313 @@
+use crate::scylla::ErrorKind;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Write},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Receives measurements of the statements executed by an
+/// [`Instance`](crate::scylla::Instance), keyed by the statement text.
+///
+/// Pagers returned by [`Instance::query_iter`](crate::scylla::Instance::query_iter) fetch pages
+/// after the call returned, only the latency and errors of the request for their first page are
+/// recorded.
+pub trait MetricsRecorder: fmt::Debug + Send + Sync {
+    /// Time taken by a call, including preparing the statement, retries and every page fetched.
+    fn latency(&self, _statement: &str, _elapsed: Duration) {}
+
+    fn error(&self, _statement: &str, _kind: ErrorKind) {}
+
+    fn rows(&self, _statement: &str, _rows: usize) {}
+
+    fn page(&self, _statement: &str) {}
+
+    /// Whether the prepared statement was found in the cache of the instance.
+    fn prepared(&self, _statement: &str, _cache_hit: bool) {}
+}
+
+/// Upper bounds of the default latency buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Keeps the measurements in memory and encodes them in the Prometheus text format.
+#[derive(Debug)]
+pub struct PrometheusRecorder {
+    buckets: Vec<f64>,
+    statements: Mutex<BTreeMap<String, Statement>>,
+}
+
+#[derive(Debug, Default)]
+struct Statement {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+    errors: BTreeMap<&'static str, u64>,
+    rows: u64,
+    pages: u64,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl Default for PrometheusRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrometheusRecorder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_buckets(BUCKETS.to_vec())
+    }
+
+    /// Latency histogram with `buckets` as upper bounds in seconds.
+    #[must_use]
+    pub fn with_buckets(mut buckets: Vec<f64>) -> Self {
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+
+        Self {
+            buckets,
+            statements: Mutex::default(),
+        }
+    }
+
+    fn update(&self, statement: &str, update: impl FnOnce(&mut Statement)) {
+        let mut statements = self
+            .statements
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(metrics) = statements.get_mut(statement) {
+            update(metrics);
+        } else {
+            let mut metrics = Statement {
+                buckets: vec![0; self.buckets.len()],
+                ..Statement::default()
+            };
+            update(&mut metrics);
+            statements.insert(statement.to_string(), metrics);
+        }
+    }
+
+    /// Current measurements in the Prometheus text exposition format.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        self.write(
+            &mut out,
+            &self
+                .statements
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+        .unwrap_or_else(|_| unreachable!("writing to a string does not fail"));
+        out
+    }
+
+    fn write(&self, out: &mut String, statements: &BTreeMap<String, Statement>) -> fmt::Result {
+        writeln!(
+            out,
+            "# HELP lib_persist_statement_duration_seconds Statement latency including retries."
+        )?;
+        writeln!(
+            out,
+            "# TYPE lib_persist_statement_duration_seconds histogram"
+        )?;
+        for (statement, metrics) in statements {
+            let statement = Label(statement);
+            for (le, count) in self.buckets.iter().zip(&metrics.buckets) {
+                writeln!(
+                    out,
+                    r#"lib_persist_statement_duration_seconds_bucket{{statement="{statement}",le="{le}"}} {count}"#
+                )?;
+            }
+            writeln!(
+                out,
+                r#"lib_persist_statement_duration_seconds_bucket{{statement="{statement}",le="+Inf"}} {}"#,
+                metrics.count
+            )?;
+            writeln!(
+                out,
+                r#"lib_persist_statement_duration_seconds_sum{{statement="{statement}"}} {}"#,
+                metrics.sum
+            )?;
+            writeln!(
+                out,
+                r#"lib_persist_statement_duration_seconds_count{{statement="{statement}"}} {}"#,
+                metrics.count
+            )?;
+        }
+
+        writeln!(
+            out,
+            "# HELP lib_persist_statement_errors_total Failed statements by kind."
+        )?;
+        writeln!(out, "# TYPE lib_persist_statement_errors_total counter")?;
+        for (statement, metrics) in statements {
+            let statement = Label(statement);
+            for (kind, count) in &metrics.errors {
+                writeln!(
+                    out,
+                    r#"lib_persist_statement_errors_total{{statement="{statement}",kind="{kind}"}} {count}"#
+                )?;
+            }
+        }
+
+        counter(out, statements, "rows", "Rows returned.", |metrics| {
+            metrics.rows
+        })?;
+        counter(out, statements, "pages", "Pages fetched.", |metrics| {
+            metrics.pages
+        })?;
+        counter(
+            out,
+            statements,
+            "prepared_cache_hits",
+            "Prepared statement cache hits.",
+            |metrics| metrics.cache_hits,
+        )?;
+        counter(
+            out,
+            statements,
+            "prepared_cache_misses",
+            "Prepared statement cache misses.",
+            |metrics| metrics.cache_misses,
+        )
+    }
+}
+
+fn counter(
+    out: &mut String,
+    statements: &BTreeMap<String, Statement>,
+    name: &str,
+    help: &str,
+    value: impl Fn(&Statement) -> u64,
+) -> fmt::Result {
+    writeln!(out, "# HELP lib_persist_statement_{name}_total {help}")?;
+    writeln!(out, "# TYPE lib_persist_statement_{name}_total counter")?;
+    for (statement, metrics) in statements {
+        writeln!(
+            out,
+            r#"lib_persist_statement_{name}_total{{statement="{}"}} {}"#,
+            Label(statement),
+            value(metrics)
+        )?;
+    }
+    Ok(())
+}
+
+impl MetricsRecorder for PrometheusRecorder {
+    fn latency(&self, statement: &str, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+
+        self.update(statement, |metrics| {
+            for (le, count) in self.buckets.iter().zip(&mut metrics.buckets) {
+                if seconds <= *le {
+                    *count += 1;
+                }
+            }
+            metrics.count += 1;
+            metrics.sum += seconds;
+        });
+    }
+
+    fn error(&self, statement: &str, kind: ErrorKind) {
+        self.update(statement, |metrics| {
+            *metrics.errors.entry(kind.as_str()).or_default() += 1;
+        });
+    }
+
+    fn rows(&self, statement: &str, rows: usize) {
+        self.update(statement, |metrics| {
+            metrics.rows += u64::try_from(rows).unwrap_or(u64::MAX);
+        });
+    }
+
+    fn page(&self, statement: &str) {
+        self.update(statement, |metrics| metrics.pages += 1);
+    }
+
+    fn prepared(&self, statement: &str, cache_hit: bool) {
+        self.update(statement, |metrics| {
+            if cache_hit {
+                metrics.cache_hits += 1;
+            } else {
+                metrics.cache_misses += 1;
+            }
+        });
+    }
+}
+
+/// Escapes a label value.
+struct Label<'a>(&'a str);
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.chars().try_for_each(|char| match char {
+            '\\' => f.write_str(r"\\"),
+            '"' => f.write_str(r#"\""#),
+            '\n' => f.write_str(r"\n"),
+            char => f.write_char(char),
+        })
+    }
+}
+
+/// Statement texts prepared through the cache of an instance, telling cache hits from misses.
+///
+/// Exact while fewer statements are prepared than the driver cache holds. Beyond that the driver
+/// evicts arbitrary entries, the texts are forgotten and the counts become approximate.
+#[derive(Debug)]
+pub struct PreparedTexts {
+    capacity: usize,
+    texts: Mutex<HashSet<String>>,
+}
+
+impl PreparedTexts {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            texts: Mutex::default(),
+        }
+    }
+
+    pub fn contains(&self, statement: &str) -> bool {
+        self.texts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(statement)
+    }
+
+    pub fn insert(&self, statement: &str) {
+        let mut texts = self.texts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !texts.contains(statement) {
+            if texts.len() >= self.capacity {
+                texts.clear();
+            }
+            texts.insert(statement.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepared_texts_forget_beyond_capacity() {
+        let texts = PreparedTexts::new(2);
+        texts.insert("a");
+        texts.insert("b");
+        texts.insert("a");
+
+        assert!(texts.contains("a") && texts.contains("b"));
+
+        texts.insert("c");
+
+        assert!(!texts.contains("a") && !texts.contains("b"));
+        assert!(texts.contains("c"));
+    }
+}
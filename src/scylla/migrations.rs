@@ -0,0 +1,241 @@
+use crate::scylla::{Conditional, Instance, MigrationError, meta_keyspace};
+use futures::future::{Either, select};
+use rand::random;
+use std::{
+    pin::pin,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use uuid::{Builder, Uuid};
+
+/// Migration files applied in order by
+/// [`Instance::migrate_all`](crate::scylla::Instance::migrate_all), usually embedded with
+/// [`migrations!`](crate::scylla::migrations).
+#[derive(Clone, Copy, Debug)]
+pub struct Migrations {
+    files: &'static [Migration],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Migration {
+    /// Name the applied statements are recorded under.
+    pub file: &'static str,
+    pub cql: &'static str,
+}
+
+impl Migrations {
+    #[must_use]
+    pub const fn new(files: &'static [Migration]) -> Self {
+        Self { files }
+    }
+
+    #[must_use]
+    pub const fn files(&self) -> &'static [Migration] {
+        self.files
+    }
+}
+
+/// Result of [`Instance::migrate_all`](crate::scylla::Instance::migrate_all).
+#[derive(Clone, Debug, Default)]
+pub struct MigrationSummary {
+    /// Files with newly applied statements and how many were applied.
+    pub applied: Vec<(&'static str, usize)>,
+    /// Files that were already applied.
+    pub unchanged: usize,
+}
+
+impl MigrationSummary {
+    /// Number of newly applied statements.
+    #[must_use]
+    pub fn statements(&self) -> usize {
+        self.applied.iter().map(|(_, statements)| statements).sum()
+    }
+}
+
+/// Lease in the meta keyspace held while migrations run, so concurrent runners of the same
+/// application apply them one at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MigrationLock {
+    pub(crate) ttl: Duration,
+    pub(crate) wait: Duration,
+    pub(crate) retry_interval: Duration,
+}
+
+impl Default for MigrationLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MigrationLock {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            ttl: Duration::from_mins(1),
+            wait: Duration::from_mins(5),
+            retry_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// How long the lease lasts, a runner that crashed holds it at most this long. The lease is
+    /// renewed every third of it while migrations run.
+    #[must_use]
+    pub const fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long to wait for another runner to finish before failing with
+    /// [`MigrationError::Locked`], zero fails immediately.
+    #[must_use]
+    pub const fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    #[must_use]
+    pub const fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+}
+
+/// Migration lock held by an instance.
+pub struct Lease<'a> {
+    instance: &'a Instance,
+    owner: Uuid,
+    acquired: Instant,
+}
+
+impl<'a> Lease<'a> {
+    pub async fn acquire(instance: &'a Instance) -> Result<Self, MigrationError> {
+        let lock = instance.migration_lock;
+        let owner = Builder::from_random_bytes(random()).into_uuid();
+        let started = Instant::now();
+
+        loop {
+            let acquired = Instant::now();
+            let holder = match update(instance, owner, None).await? {
+                Conditional::Applied => {
+                    debug!(%owner, "Acquired migration lock");
+                    return Ok(Self {
+                        instance,
+                        owner,
+                        acquired,
+                    });
+                }
+                Conditional::NotApplied(holder) => holder.and_then(|(holder,)| holder),
+            };
+
+            if started.elapsed() + lock.retry_interval > lock.wait {
+                return Err(MigrationError::Locked { owner: holder });
+            }
+            debug!(holder = ?holder, "Waiting for migration lock");
+            sleep(lock.retry_interval).await;
+        }
+    }
+
+    /// Runs `work` while renewing the lease in the background, `work` is dropped as soon as the
+    /// lease is lost.
+    pub async fn hold<T>(
+        &self,
+        work: impl Future<Output = Result<T, MigrationError>>,
+    ) -> Result<T, MigrationError> {
+        match select(pin!(work), pin!(self.heartbeat())).await {
+            Either::Left((result, _)) => result,
+            Either::Right((error, _)) => Err(error),
+        }
+    }
+
+    /// Extends the lease and fails with [`MigrationError::LockLost`] when another runner took
+    /// it over.
+    pub async fn renew(&self) -> Result<(), MigrationError> {
+        match update(self.instance, self.owner, Some(self.owner)).await? {
+            Conditional::Applied => Ok(()),
+            Conditional::NotApplied(_) => Err(MigrationError::LockLost),
+        }
+    }
+
+    /// Renews the lease every third of its ttl, failed renewals are retried until it expires.
+    async fn heartbeat(&self) -> MigrationError {
+        let ttl = self.instance.migration_lock.ttl;
+        let mut renewed = self.acquired;
+
+        loop {
+            sleep(ttl / 3).await;
+
+            let started = Instant::now();
+            match self.renew().await {
+                Ok(()) => renewed = started,
+                Err(MigrationError::LockLost) => return MigrationError::LockLost,
+                Err(error) if renewed.elapsed() + ttl / 3 >= ttl => {
+                    warn!(
+                        error = &error as &dyn std::error::Error,
+                        "Migration lock expired"
+                    );
+                    return MigrationError::LockLost;
+                }
+                Err(error) => warn!(
+                    error = &error as &dyn std::error::Error,
+                    "Could not renew migration lock"
+                ),
+            }
+        }
+    }
+
+    pub async fn release(self) -> Result<(), MigrationError> {
+        let meta_keyspace = meta_keyspace();
+        self.instance
+            .inner
+            .get_session()
+            .query_unpaged(
+                format!(
+                    "delete owner from {meta_keyspace}.migration_lock
+                       where app_instance = ? and app_name = ? if owner = ?"
+                ),
+                (
+                    self.instance.app_instance,
+                    self.instance.app_name.as_ref(),
+                    self.owner,
+                ),
+            )
+            .await?;
+
+        debug!(owner = %self.owner, "Released migration lock");
+        Ok(())
+    }
+}
+
+/// Sets `owner` as holder of the lock when it's held by `expected`, or free when `None`.
+async fn update(
+    instance: &Instance,
+    owner: Uuid,
+    expected: Option<Uuid>,
+) -> Result<Conditional<(Option<Uuid>,)>, MigrationError> {
+    let meta_keyspace = meta_keyspace();
+    let ttl = i32::try_from(instance.migration_lock.ttl.as_secs())
+        .unwrap_or(i32::MAX)
+        .max(1);
+
+    Ok(instance
+        .inner
+        .get_session()
+        .query_unpaged(
+            format!(
+                "update {meta_keyspace}.migration_lock using ttl ? set owner = ?
+                   where app_instance = ? and app_name = ? if owner = ?"
+            ),
+            (
+                ttl,
+                owner,
+                instance.app_instance,
+                instance.app_name.as_ref(),
+                expected,
+            ),
+        )
+        .await?
+        .into_rows_result()?
+        .maybe_first_row()?
+        .unwrap_or(Conditional::NotApplied(None)))
+}
@@ -0,0 +1,181 @@
+use crate::scylla::{RetryPolicy, SpeculativeExecution, retry::SpeculativeProfiles};
+use scylla::statement::{Consistency, SerialConsistency, Statement, batch::Batch};
+use std::{num::NonZeroU32, time::Duration};
+
+/// Execution options for a single statement, unset values fall back to the defaults of the
+/// [`Instance`](crate::scylla::Instance) and then to the session defaults.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QueryOptions {
+    consistency: Option<Consistency>,
+    serial_consistency: Option<SerialConsistency>,
+    request_timeout: Option<Duration>,
+    page_size: Option<NonZeroU32>,
+    idempotent: Option<bool>,
+    tracing: Option<bool>,
+    timestamp: Option<i64>,
+    retry_policy: Option<RetryPolicy>,
+    speculative_execution: Option<SpeculativeExecution>,
+}
+
+impl QueryOptions {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            consistency: None,
+            serial_consistency: None,
+            request_timeout: None,
+            page_size: None,
+            idempotent: None,
+            tracing: None,
+            timestamp: None,
+            retry_policy: None,
+            speculative_execution: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = Some(consistency);
+        self
+    }
+
+    #[must_use]
+    pub const fn serial_consistency(mut self, serial_consistency: SerialConsistency) -> Self {
+        self.serial_consistency = Some(serial_consistency);
+        self
+    }
+
+    #[must_use]
+    pub const fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Number of rows fetched per page, capped at `i32::MAX`.
+    #[must_use]
+    pub const fn page_size(mut self, page_size: NonZeroU32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    #[must_use]
+    pub const fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = Some(idempotent);
+        self
+    }
+
+    /// Records a trace of the request, read it with
+    /// [`Instance::fetch_trace`](crate::scylla::Instance::fetch_trace).
+    #[must_use]
+    pub const fn tracing(mut self, tracing: bool) -> Self {
+        self.tracing = Some(tracing);
+        self
+    }
+
+    /// Write timestamp in microseconds since the unix epoch.
+    #[must_use]
+    pub const fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Only applied when the statement is idempotent.
+    #[must_use]
+    pub const fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Only applied when the statement is idempotent.
+    #[must_use]
+    pub const fn speculative_execution(mut self, policy: SpeculativeExecution) -> Self {
+        self.speculative_execution = Some(policy);
+        self
+    }
+
+    /// Values set in `self` take precedence over the ones in `defaults`.
+    #[must_use]
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            consistency: self.consistency.or(defaults.consistency),
+            serial_consistency: self.serial_consistency.or(defaults.serial_consistency),
+            request_timeout: self.request_timeout.or(defaults.request_timeout),
+            page_size: self.page_size.or(defaults.page_size),
+            idempotent: self.idempotent.or(defaults.idempotent),
+            tracing: self.tracing.or(defaults.tracing),
+            timestamp: self.timestamp.or(defaults.timestamp),
+            retry_policy: self.retry_policy.or(defaults.retry_policy),
+            speculative_execution: self
+                .speculative_execution
+                .or(defaults.speculative_execution),
+        }
+    }
+
+    /// Retry policy the instance waits between retries for, `None` unless `idempotent`.
+    pub(crate) fn backoff(&self, idempotent: bool) -> Option<RetryPolicy> {
+        self.retry_policy.filter(|_| idempotent)
+    }
+
+    pub(crate) fn apply(&self, statement: &mut Statement, profiles: &SpeculativeProfiles) {
+        if let Some(consistency) = self.consistency {
+            statement.set_consistency(consistency);
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            statement.set_serial_consistency(Some(serial_consistency));
+        }
+        if let Some(timeout) = self.request_timeout {
+            statement.set_request_timeout(Some(timeout));
+        }
+        if let Some(page_size) = self.page_size {
+            statement.set_page_size(i32::try_from(page_size.get()).unwrap_or(i32::MAX));
+        }
+        if let Some(idempotent) = self.idempotent {
+            statement.set_is_idempotent(idempotent);
+        }
+        if let Some(tracing) = self.tracing {
+            statement.set_tracing(tracing);
+        }
+        if let Some(timestamp) = self.timestamp {
+            statement.set_timestamp(Some(timestamp));
+        }
+        if statement.get_is_idempotent() {
+            if let Some(policy) = self.retry_policy {
+                statement.set_retry_policy(Some(policy.driver_policy()));
+            }
+            if let Some(policy) = self.speculative_execution {
+                let profile = profiles.profile(policy, statement.get_execution_profile_handle());
+                statement.set_execution_profile_handle(Some(profile));
+            }
+        }
+    }
+
+    pub(crate) fn apply_batch(&self, batch: &mut Batch, profiles: &SpeculativeProfiles) {
+        if let Some(consistency) = self.consistency {
+            batch.set_consistency(consistency);
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            batch.set_serial_consistency(Some(serial_consistency));
+        }
+        if let Some(timeout) = self.request_timeout {
+            batch.set_request_timeout(Some(timeout));
+        }
+        if let Some(idempotent) = self.idempotent {
+            batch.set_is_idempotent(idempotent);
+        }
+        if let Some(tracing) = self.tracing {
+            batch.set_tracing(tracing);
+        }
+        if let Some(timestamp) = self.timestamp {
+            batch.set_timestamp(Some(timestamp));
+        }
+        if batch.get_is_idempotent() {
+            if let Some(policy) = self.retry_policy {
+                batch.set_retry_policy(Some(policy.driver_policy()));
+            }
+            if let Some(policy) = self.speculative_execution {
+                let profile = profiles.profile(policy, batch.get_execution_profile_handle());
+                batch.set_execution_profile_handle(Some(profile));
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use scylla::statement::{Statement, prepared::PreparedStatement};
+use std::fmt;
+
+/// Statement validated by [`Instance::prepare_all`](crate::scylla::Instance::prepare_all).
+///
+/// Converts into a [`Statement`] so it can be passed to every query method, which execute it
+/// through the prepared statement cache.
+#[derive(Clone)]
+pub struct Prepared {
+    statement: Statement,
+    prepared: PreparedStatement,
+}
+
+impl Prepared {
+    pub(crate) const fn new(statement: Statement, prepared: PreparedStatement) -> Self {
+        Self {
+            statement,
+            prepared,
+        }
+    }
+
+    #[must_use]
+    pub fn contents(&self) -> &str {
+        &self.statement.contents
+    }
+
+    #[must_use]
+    pub const fn prepared(&self) -> &PreparedStatement {
+        &self.prepared
+    }
+}
+
+impl fmt::Debug for Prepared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prepared")
+            .field("contents", &self.statement.contents)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<&Prepared> for Statement {
+    fn from(prepared: &Prepared) -> Self {
+        prepared.statement.clone()
+    }
+}
+
+impl From<Prepared> for Statement {
+    fn from(prepared: Prepared) -> Self {
+        prepared.statement
+    }
+}
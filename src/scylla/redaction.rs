@@ -0,0 +1,221 @@
+use crate::scylla::script::quoted;
+use scylla::errors::{
+    ExecutionError, NextPageError, PagerExecutionError, PrepareError, RequestAttemptError,
+    RequestError,
+};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, fmt::Write, str::FromStr};
+
+/// How statement text appears in logs, metrics and errors. Unless `Off`, the messages of server
+/// errors are dropped as well, since they can quote the statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Redaction {
+    #[default]
+    Off,
+    /// String, number, blob and uuid literals are replaced with `?`.
+    Literals,
+    /// Only a hash of the statement is shown.
+    Hash,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "literals" => Ok(Self::Literals),
+            "hash" => Ok(Self::Hash),
+            value => Err(format!("unknown redaction '{value}'")),
+        }
+    }
+}
+
+impl Redaction {
+    #[must_use]
+    pub fn redact(self, statement: &str) -> Cow<'_, str> {
+        match self {
+            Self::Off => Cow::Borrowed(statement),
+            Self::Literals => Cow::Owned(strip_literals(statement)),
+            Self::Hash => Cow::Owned(Sha256::digest(statement.as_bytes())[..8].iter().fold(
+                String::from("sha256:"),
+                |mut hash, byte| {
+                    let _ = write!(hash, "{byte:02x}");
+                    hash
+                },
+            )),
+        }
+    }
+
+    /// Drops the message of errors returned by the server, which can quote any part of the
+    /// statement.
+    pub(crate) fn execution_error(self, err: ExecutionError) -> ExecutionError {
+        match err {
+            ExecutionError::LastAttemptError(err) => {
+                ExecutionError::LastAttemptError(self.attempt_error(err))
+            }
+            ExecutionError::PrepareError(err) => {
+                ExecutionError::PrepareError(self.prepare_error(err))
+            }
+            err => err,
+        }
+    }
+
+    pub(crate) fn prepare_error(self, err: PrepareError) -> PrepareError {
+        match err {
+            PrepareError::AllAttemptsFailed { first_attempt } => PrepareError::AllAttemptsFailed {
+                first_attempt: self.attempt_error(first_attempt),
+            },
+            err => err,
+        }
+    }
+
+    pub(crate) fn pager_error(self, err: PagerExecutionError) -> PagerExecutionError {
+        match err {
+            PagerExecutionError::PrepareError(err) => {
+                PagerExecutionError::PrepareError(self.prepare_error(err))
+            }
+            PagerExecutionError::NextPageError(err) => {
+                PagerExecutionError::NextPageError(self.next_page_error(err))
+            }
+            err => err,
+        }
+    }
+
+    fn next_page_error(self, err: NextPageError) -> NextPageError {
+        match err {
+            NextPageError::RequestFailure(RequestError::LastAttemptError(err)) => {
+                NextPageError::RequestFailure(RequestError::LastAttemptError(
+                    self.attempt_error(err),
+                ))
+            }
+            err => err,
+        }
+    }
+
+    fn attempt_error(self, err: RequestAttemptError) -> RequestAttemptError {
+        match err {
+            RequestAttemptError::DbError(err, _) if self != Self::Off => {
+                RequestAttemptError::DbError(err, "<redacted>".into())
+            }
+            err => err,
+        }
+    }
+}
+
+fn strip_literals(statement: &str) -> String {
+    let bytes = statement.as_bytes();
+    let mut out = String::with_capacity(statement.len());
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let literal_end = match bytes[i] {
+            b'\'' => Some(quoted(bytes, i).unwrap_or(bytes.len())),
+            b'$' if bytes.get(i + 1) == Some(&b'$') => Some(
+                statement[i + 2..]
+                    .find("$$")
+                    .map_or(bytes.len(), |end| i + 2 + end + 2),
+            ),
+            b'"' => {
+                i = quoted(bytes, i).unwrap_or(bytes.len());
+                continue;
+            }
+            _ if in_word(bytes, i) => None,
+            _ if is_uuid(&bytes[i..]) => Some(i + 36),
+            b'0'..=b'9' => Some(number(bytes, i)),
+            _ => None,
+        };
+
+        if let Some(end) = literal_end {
+            out.push_str(&statement[start..i]);
+            out.push('?');
+            start = end;
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    out.push_str(&statement[start..]);
+    out
+}
+
+fn number(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+
+    while i < bytes.len()
+        && (is_word(bytes[i])
+            || bytes[i] == b'.'
+            || (bytes[i] == b'+' || bytes[i] == b'-') && matches!(bytes[i - 1], b'e' | b'E'))
+    {
+        i += 1;
+    }
+
+    i
+}
+
+fn is_uuid(bytes: &[u8]) -> bool {
+    bytes.len() >= 36
+        && bytes[..36].iter().enumerate().all(|(i, byte)| match i {
+            8 | 13 | 18 | 23 => *byte == b'-',
+            _ => byte.is_ascii_hexdigit(),
+        })
+        && !bytes.get(36).copied().is_some_and(is_word)
+}
+
+fn in_word(bytes: &[u8], i: usize) -> bool {
+    i > 0 && is_word(bytes[i - 1])
+}
+
+const fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_literals() {
+        assert_eq!(
+            strip_literals(
+                "insert into t (id, name, size, ratio, data) \
+                 values (550e8400-e29b-41d4-a716-446655440000, 'it''s', -12, 1.5e-3, 0xcafe)"
+            ),
+            "insert into t (id, name, size, ratio, data) values (?, ?, -?, ?, ?)"
+        );
+    }
+
+    #[test]
+    fn keeps_identifiers() {
+        assert_eq!(
+            strip_literals(r#"select "Col 'x'", v2 from t1 where k = $$secret$$"#),
+            r#"select "Col 'x'", v2 from t1 where k = ?"#
+        );
+    }
+
+    #[test]
+    fn strips_unterminated_strings() {
+        assert_eq!(
+            strip_literals("select * from t where k = 'secret"),
+            "select * from t where k = ?"
+        );
+    }
+
+    #[test]
+    fn drops_server_messages() {
+        let message = |redaction: Redaction| {
+            let error = ExecutionError::LastAttemptError(RequestAttemptError::DbError(
+                scylla::errors::DbError::Invalid,
+                "Undefined name x in where clause ('x = 'secret'')".into(),
+            ));
+
+            redaction.execution_error(error).to_string()
+        };
+
+        assert!(message(Redaction::Off).contains("secret"));
+        assert!(!message(Redaction::Literals).contains("secret"));
+        assert!(!message(Redaction::Hash).contains("secret"));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::scylla::{Classify, Error};
+use scylla::{
+    client::execution_profile::ExecutionProfileHandle,
+    policies::{
+        retry::{DowngradingConsistencyRetryPolicy, FallthroughRetryPolicy},
+        speculative_execution::{
+            PercentileSpeculativeExecutionPolicy, SimpleSpeculativeExecutionPolicy,
+            SpeculativeExecutionPolicy,
+        },
+    },
+};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::time::sleep;
+use tracing::warn;
+
+/// How failed requests are retried. Only applied to statements marked idempotent, the others
+/// keep the driver default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryPolicy {
+    Off,
+    /// Retries up to `attempts` times, waiting `delay` before each retry.
+    Fixed {
+        attempts: u32,
+        delay: Duration,
+    },
+    /// Retries up to `attempts` times, doubling the delay from `initial_delay` up to `max_delay`.
+    Exponential {
+        attempts: u32,
+        initial_delay: Duration,
+        max_delay: Duration,
+    },
+    /// Retries once with a lower consistency when too few replicas responded.
+    DowngradingConsistency,
+}
+
+impl RetryPolicy {
+    /// Policy used by the driver, the instance waits between retries itself so the driver only
+    /// retries for [`RetryPolicy::DowngradingConsistency`].
+    pub(crate) fn driver_policy(self) -> Arc<dyn scylla::policies::retry::RetryPolicy> {
+        match self {
+            Self::DowngradingConsistency => Arc::new(DowngradingConsistencyRetryPolicy::new()),
+            Self::Off | Self::Fixed { .. } | Self::Exponential { .. } => {
+                Arc::new(FallthroughRetryPolicy::new())
+            }
+        }
+    }
+
+    /// Delay before retry `attempt`, starting at 1, or `None` when the request is not retried.
+    fn delay(self, attempt: u32) -> Option<Duration> {
+        match self {
+            Self::Fixed { attempts, delay } if attempt <= attempts => Some(delay),
+            Self::Exponential {
+                attempts,
+                initial_delay,
+                max_delay,
+            } if attempt <= attempts => Some(
+                initial_delay
+                    .saturating_mul(2_u32.saturating_pow(attempt - 1))
+                    .min(max_delay),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Additional requests sent to other replicas when the first one is slow to respond. Only
+/// applied to statements marked idempotent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeculativeExecution {
+    /// Sends up to `max_retry_count` additional requests, one every `retry_interval`.
+    Simple {
+        max_retry_count: usize,
+        retry_interval: Duration,
+    },
+    /// Sends up to `max_retry_count` additional requests, one whenever the request has taken
+    /// longer than `percentile` of the recent request latencies.
+    Percentile {
+        max_retry_count: usize,
+        percentile: f64,
+    },
+}
+
+impl SpeculativeExecution {
+    /// Copy of `profile` that uses this policy.
+    pub(crate) fn profile(self, profile: &ExecutionProfileHandle) -> ExecutionProfileHandle {
+        let policy: Arc<dyn SpeculativeExecutionPolicy> = match self {
+            Self::Simple {
+                max_retry_count,
+                retry_interval,
+            } => Arc::new(SimpleSpeculativeExecutionPolicy {
+                max_retry_count,
+                retry_interval,
+            }),
+            Self::Percentile {
+                max_retry_count,
+                percentile,
+            } => Arc::new(PercentileSpeculativeExecutionPolicy {
+                max_retry_count,
+                percentile,
+            }),
+        };
+
+        profile
+            .pointee_to_builder()
+            .speculative_execution_policy(Some(policy))
+            .build()
+            .into_handle()
+    }
+}
+
+/// Execution profiles with speculative execution, derived from the session default profile once
+/// per policy so requests don't build a new profile each time.
+#[derive(Debug)]
+pub struct SpeculativeProfiles {
+    default: ExecutionProfileHandle,
+    profiles: Mutex<Vec<(SpeculativeExecution, ExecutionProfileHandle)>>,
+}
+
+impl SpeculativeProfiles {
+    /// `policy` is the instance default, its profile is built up front.
+    pub fn new(default: ExecutionProfileHandle, policy: Option<SpeculativeExecution>) -> Self {
+        let profiles = policy
+            .map(|policy| (policy, policy.profile(&default)))
+            .into_iter()
+            .collect();
+
+        Self {
+            default,
+            profiles: Mutex::new(profiles),
+        }
+    }
+
+    /// Profile using `policy`, based on the profile set on the statement when there is one and
+    /// on the session default otherwise. Only the latter are cached.
+    pub fn profile(
+        &self,
+        policy: SpeculativeExecution,
+        statement_profile: Option<&ExecutionProfileHandle>,
+    ) -> ExecutionProfileHandle {
+        if let Some(profile) = statement_profile {
+            return policy.profile(profile);
+        }
+
+        let mut profiles = self.profiles.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, profile)) = profiles.iter().find(|(cached, _)| *cached == policy) {
+            return profile.clone();
+        }
+
+        let profile = policy.profile(&self.default);
+        profiles.push((policy, profile.clone()));
+        profile
+    }
+}
+
+/// Runs `execute` until it succeeds, fails with an error that isn't retryable or `policy` gives
+/// up. `policy` is `None` for statements that are not idempotent.
+pub async fn retry<T, F>(
+    policy: Option<RetryPolicy>,
+    mut execute: impl FnMut() -> F,
+) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let error = match execute().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        match policy.and_then(|policy| policy.delay(attempt)) {
+            Some(delay) if error.is_retryable() => {
+                warn!(
+                    attempt,
+                    ?delay,
+                    error = &error as &dyn std::error::Error,
+                    "Request failed, retrying"
+                );
+                sleep(delay).await;
+            }
+            _ => return Err(error),
+        }
+    }
+}
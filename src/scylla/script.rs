@@ -0,0 +1,169 @@
+use crate::scylla::ScriptError;
+
+/// Splits a CQL script into its statements at the semicolons outside of strings, quoted
+/// identifiers, `$$` blocks and comments. Statements are trimmed and those that only hold
+/// comments are skipped.
+pub fn split(script: &str) -> Result<Vec<&str>, ScriptError> {
+    let bytes = script.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut code = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b';', _) => {
+                if code {
+                    statements.push(script[start..i].trim());
+                }
+                code = false;
+                start = i + 1;
+                i += 1;
+            }
+            (b'\'', _) => {
+                i = quoted(bytes, i).ok_or_else(|| error(script, i, "string"))?;
+                code = true;
+            }
+            (b'"', _) => {
+                i = quoted(bytes, i).ok_or_else(|| error(script, i, "quoted identifier"))?;
+                code = true;
+            }
+            (b'$', Some(b'$')) => {
+                i = closed(script, i, "$$", "$$ block")?;
+                code = true;
+            }
+            (b'-', Some(b'-')) | (b'/', Some(b'/')) => {
+                i = script[i..]
+                    .find('\n')
+                    .map_or(bytes.len(), |end| i + end + 1);
+            }
+            (b'/', Some(b'*')) => i = closed(script, i, "*/", "comment")?,
+            (byte, _) => {
+                code |= !byte.is_ascii_whitespace();
+                i += 1;
+            }
+        }
+    }
+
+    if code {
+        statements.push(script[start..].trim());
+    }
+    Ok(statements)
+}
+
+/// End of the string or quoted identifier starting at `start`, quotes are escaped by doubling
+/// them. `None` when it's never closed.
+pub fn quoted(bytes: &[u8], start: usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) != Some(&quote) {
+                return Some(i + 1);
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// End of the block opened at `start` and closed by `end`.
+fn closed(
+    script: &str,
+    start: usize,
+    end: &str,
+    token: &'static str,
+) -> Result<usize, ScriptError> {
+    script[start + 2..]
+        .find(end)
+        .map(|offset| start + 2 + offset + end.len())
+        .ok_or_else(|| error(script, start, token))
+}
+
+fn error(script: &str, offset: usize, token: &'static str) -> ScriptError {
+    let before = &script[..offset];
+
+    ScriptError {
+        token,
+        line: before.matches('\n').count() + 1,
+        column: before
+            .rsplit('\n')
+            .next()
+            .map_or(0, |line| line.chars().count())
+            + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_semicolons() {
+        assert_eq!(
+            split("create table a (id int);\n\n insert into a (id) values (1) ;").ok(),
+            Some(vec![
+                "create table a (id int)",
+                "insert into a (id) values (1)"
+            ])
+        );
+    }
+
+    #[test]
+    fn ignores_semicolons_in_strings_and_identifiers() {
+        assert_eq!(
+            split(r#"insert into "a;b" (v) values ('x;''y'); select * from a"#).ok(),
+            Some(vec![
+                r#"insert into "a;b" (v) values ('x;''y')"#,
+                "select * from a"
+            ])
+        );
+    }
+
+    #[test]
+    fn ignores_semicolons_in_dollar_blocks() {
+        let function = "create function f() returns null on null input returns int \
+                        language lua as $$ return 1; $$";
+
+        assert_eq!(
+            split(&format!("{function};select 1")).ok(),
+            Some(vec![function, "select 1"])
+        );
+    }
+
+    #[test]
+    fn ignores_semicolons_in_comments() {
+        assert_eq!(
+            split("-- a;\nselect 1; // b;\nselect 2 /* c; */; /* d; */").ok(),
+            Some(vec!["-- a;\nselect 1", "// b;\nselect 2 /* c; */"])
+        );
+    }
+
+    #[test]
+    fn skips_statements_with_only_comments() {
+        assert_eq!(split("-- a;\n/* b */;\n ;").ok(), Some(vec![]));
+    }
+
+    #[test]
+    fn reports_unterminated_tokens() {
+        for (script, token, line, column) in [
+            ("select 'a", "string", 1, 8),
+            ("select 1;\nselect \"a", "quoted identifier", 2, 8),
+            ("select 1;\n  $$ a", "$$ block", 2, 3),
+            ("select 1;\n\n/* a", "comment", 3, 1),
+        ] {
+            let error = split(script).err();
+
+            assert_eq!(
+                error
+                    .as_ref()
+                    .map(|error| (error.token, error.line(), error.column())),
+                Some((token, line, column)),
+                "{script}"
+            );
+        }
+    }
+}
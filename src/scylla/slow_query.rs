@@ -0,0 +1,104 @@
+use rand::random_bool;
+use scylla::statement::{Consistency, prepared::PreparedStatement};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Logs a warning for statements that take longer than a threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlowQueryLog {
+    pub(crate) threshold: Duration,
+    pub(crate) trace_sample_rate: f64,
+}
+
+impl SlowQueryLog {
+    #[must_use]
+    pub const fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            trace_sample_rate: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub const fn threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Fraction of the statements executed with driver tracing enabled, clamped to `0.0..=1.0`.
+    /// The warning of a traced statement includes its tracing id.
+    #[must_use]
+    pub const fn trace_sample_rate(mut self, rate: f64) -> Self {
+        self.trace_sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Whether the next execution should be traced.
+    pub(crate) fn sample(self) -> bool {
+        self.trace_sample_rate > 0.0 && random_bool(self.trace_sample_rate)
+    }
+
+    pub(crate) fn log<E: std::error::Error + 'static>(
+        self,
+        statement: &str,
+        elapsed: Duration,
+        execution: Result<&Execution, &E>,
+    ) {
+        if elapsed < self.threshold {
+            return;
+        }
+
+        match execution {
+            Ok(execution) => warn!(
+                statement,
+                bound_values = execution.bound_values,
+                ?elapsed,
+                consistency = %execution.consistency,
+                pages = execution.pages,
+                tracing_id = execution.tracing_id.map(tracing::field::display),
+                "Slow query"
+            ),
+            Err(error) => warn!(
+                statement,
+                ?elapsed,
+                error = error as &dyn std::error::Error,
+                "Slow query"
+            ),
+        }
+    }
+}
+
+/// What an executed statement did, included in the slow query log.
+#[derive(Debug)]
+pub struct Execution {
+    bound_values: usize,
+    consistency: Consistency,
+    pages: usize,
+    tracing_id: Option<Uuid>,
+}
+
+impl Execution {
+    /// `consistency` is used when the statement doesn't set one.
+    pub fn new(prepared: &PreparedStatement, consistency: Consistency) -> Self {
+        Self::with_values(
+            prepared.get_variable_col_specs().len(),
+            prepared.get_consistency().unwrap_or(consistency),
+        )
+    }
+
+    pub const fn with_values(bound_values: usize, consistency: Consistency) -> Self {
+        Self {
+            bound_values,
+            consistency,
+            pages: 0,
+            tracing_id: None,
+        }
+    }
+
+    /// Counts a fetched page, keeping the tracing id of the first traced page.
+    pub fn page(&mut self, tracing_id: Option<Uuid>) {
+        self.pages += 1;
+        self.tracing_id = self.tracing_id.or(tracing_id);
+    }
+}
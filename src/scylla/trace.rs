@@ -0,0 +1,82 @@
+use scylla::{
+    observability::tracing::{TracingEvent, TracingInfo},
+    value::CqlTimestamp,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// Request trace read by [`Instance::fetch_trace`](crate::scylla::Instance::fetch_trace).
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pub id: Uuid,
+    pub coordinator: Option<IpAddr>,
+    pub client: Option<IpAddr>,
+    /// Kind of request, such as `Execute CQL3 query`.
+    pub request: Option<String>,
+    /// Consistency, page size and statement text of the request.
+    pub parameters: HashMap<String, String>,
+    pub started_at: Option<CqlTimestamp>,
+    /// Total time spent by the coordinator.
+    pub duration: Option<Duration>,
+    pub events: Vec<TraceEvent>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TraceEvent {
+    pub activity: Option<String>,
+    /// Node that recorded the event.
+    pub source: Option<IpAddr>,
+    /// Time since the node started working on the request.
+    pub elapsed: Option<Duration>,
+    pub thread: Option<String>,
+}
+
+impl Trace {
+    pub(crate) fn new(id: Uuid, info: TracingInfo) -> Self {
+        Self {
+            id,
+            coordinator: info.coordinator,
+            client: info.client,
+            request: info.request,
+            parameters: info.parameters.unwrap_or_default(),
+            started_at: info.started_at,
+            duration: info.duration.and_then(micros),
+            events: info.events.into_iter().map(TraceEvent::from).collect(),
+        }
+    }
+
+    /// Time each node spent on the request, up to the last event it recorded.
+    #[must_use]
+    pub fn durations(&self) -> BTreeMap<IpAddr, Duration> {
+        self.events
+            .iter()
+            .fold(BTreeMap::new(), |mut nodes, event| {
+                if let (Some(source), Some(elapsed)) = (event.source, event.elapsed) {
+                    nodes
+                        .entry(source)
+                        .and_modify(|duration: &mut Duration| *duration = (*duration).max(elapsed))
+                        .or_insert(elapsed);
+                }
+                nodes
+            })
+    }
+}
+
+impl From<TracingEvent> for TraceEvent {
+    fn from(event: TracingEvent) -> Self {
+        Self {
+            activity: event.activity,
+            source: event.source,
+            elapsed: event.source_elapsed.and_then(micros),
+            thread: event.thread,
+        }
+    }
+}
+
+fn micros(micros: i32) -> Option<Duration> {
+    u64::try_from(micros).ok().map(Duration::from_micros)
+}